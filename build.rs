@@ -0,0 +1,27 @@
+//! Record the versions of our model compiling dependencies
+//!
+//! `sarzak doctor` reports these, and compares them against the package it's
+//! run in.
+use std::fs;
+
+#[allow(dead_code)]
+#[path = "src/lockfile.rs"]
+mod lockfile;
+
+fn main() {
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    println!("cargo:rerun-if-changed=src/lockfile.rs");
+
+    let lock = fs::read_to_string("Cargo.lock").unwrap_or_default();
+    let lock = lockfile::Lockfile::parse(&lock).unwrap_or_default();
+
+    for name in ["sarzak", "grace", "nut"] {
+        let package = lock.find(name);
+        let version = package.map_or("unknown", |p| p.version.as_str());
+        let revision = package.and_then(|p| p.revision()).unwrap_or_default();
+
+        let name = name.to_uppercase();
+        println!("cargo:rustc-env=SARZAK_CLI_{}_VERSION={}", name, version);
+        println!("cargo:rustc-env=SARZAK_CLI_{}_REVISION={}", name, revision);
+    }
+}
@@ -0,0 +1,236 @@
+//! Safe file system updates
+//!
+//! Writes go to a temporary file, or directory, next to their destination, and
+//! are then renamed into place. A rename within a directory is atomic, so
+//! readers see either the old thing or the new thing, and never half of one.
+//!
+//! The model compiler writes its own files, so we can't do that for generated
+//! code. Instead we take a [`Snapshot`] of the module before compiling, and put
+//! it back if something goes wrong. That includes the compiler panicking, since
+//! a snapshot that's dropped without being discarded restores itself.
+use std::{
+    ffi::OsString,
+    fs,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+use anyhow::{Context, Result};
+
+/// Write a file atomically
+///
+/// If the file already exists, the new one gets its permissions.
+pub fn write_atomic<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> Result<()> {
+    let path = path.as_ref();
+    let tmp = sibling(path, "tmp");
+
+    let result = (|| -> Result<()> {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(contents.as_ref())?;
+        file.sync_all()?;
+        if let Ok(metadata) = fs::metadata(path) {
+            fs::set_permissions(&tmp, metadata.permissions())?;
+        }
+        fs::rename(&tmp, path)?;
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+
+    result.context(format!("😱 Failed to write file: {:?}", path))
+}
+
+/// Replace a file or directory atomically
+///
+/// `write` is handed a temporary path to write to. If it succeeds, whatever is
+/// at `path` is replaced with what was written. If it fails, `path` is left
+/// alone.
+///
+/// Replacing a directory takes two renames, so there is a moment where `path`
+/// doesn't exist. It's never half written though.
+pub fn replace_atomic<P, F>(path: P, write: F) -> Result<()>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&Path) -> Result<()>,
+{
+    let path = path.as_ref();
+    let tmp = sibling(path, "tmp");
+    let old = sibling(path, "old");
+
+    remove(&tmp)?;
+    if let Err(e) = write(&tmp) {
+        let _ = remove(&tmp);
+        return Err(e);
+    }
+
+    if path.is_dir() {
+        remove(&old)?;
+        fs::rename(path, &old).context(format!("😱 Failed to move {:?} aside", path))?;
+        fs::rename(&tmp, path).context(format!("😱 Failed to move {:?} into place", path))?;
+        remove(&old)?;
+    } else {
+        fs::rename(&tmp, path).context(format!("😱 Failed to move {:?} into place", path))?;
+    }
+
+    Ok(())
+}
+
+/// A copy of some files, to put back if things go sideways
+///
+/// Unless it's discarded, or restored explicitly, the snapshot is restored when
+/// it's dropped. That way a panic while the files are being written doesn't
+/// leave them half done.
+pub struct Snapshot {
+    store: PathBuf,
+    paths: Vec<PathBuf>,
+    done: bool,
+}
+
+impl Snapshot {
+    /// Copy `paths` into `store`
+    ///
+    /// Paths that don't exist are remembered, so that they can be removed
+    /// when the snapshot is restored.
+    pub fn take<P: AsRef<Path>>(store: P, paths: &[PathBuf]) -> Result<Self> {
+        let store = store.as_ref().to_path_buf();
+
+        remove(&store)?;
+        fs::create_dir_all(&store)
+            .context(format!("😱 Failed to create directory: {:?}", store))?;
+
+        for (i, path) in paths.iter().enumerate() {
+            if path.exists() {
+                copy_all(path, &store.join(i.to_string()))?;
+            }
+        }
+
+        Ok(Snapshot {
+            store,
+            paths: paths.to_vec(),
+            done: false,
+        })
+    }
+
+    /// Put everything back the way it was
+    ///
+    pub fn restore(mut self) -> Result<()> {
+        self.done = true;
+        self.put_back()
+    }
+
+    /// Throw the snapshot away
+    ///
+    pub fn discard(mut self) -> Result<()> {
+        self.done = true;
+        remove(&self.store)
+    }
+
+    fn put_back(&self) -> Result<()> {
+        for (i, path) in self.paths.iter().enumerate() {
+            let saved = self.store.join(i.to_string());
+            remove(path)?;
+            if saved.exists() {
+                fs::rename(&saved, path)
+                    .or_else(|_| copy_all(&saved, path))
+                    .context(format!("😱 Failed to restore {:?}", path))?;
+            }
+        }
+
+        remove(&self.store)
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        if !self.done {
+            let _ = self.put_back();
+        }
+    }
+}
+
+/// An exclusive lock on a package
+///
+/// This is just a file that we create, and delete when we're done. If we are
+/// killed without a chance to clean up, the file needs to be deleted by hand.
+pub struct Lock {
+    path: PathBuf,
+}
+
+impl Lock {
+    pub fn acquire<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context(format!("😱 Failed to create directory: {:?}", parent))?;
+        }
+
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(mut file) => {
+                writeln!(file, "{}", process::id())?;
+                Ok(Lock { path })
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                let pid = fs::read_to_string(&path).unwrap_or_default();
+                anyhow::bail!(
+                    "😱 {} is locked by process {}. If that process is gone, delete the lock \
+                     file and try again.",
+                    path.display(),
+                    pid.trim()
+                )
+            }
+            Err(e) => Err(e).context(format!("😱 Failed to create lock: {:?}", path)),
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// A hidden path next to `path`
+///
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(".{}-{}", suffix, process::id()));
+
+    path.with_file_name(name)
+}
+
+fn remove(path: &Path) -> Result<()> {
+    let result = if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+
+    match result {
+        Err(e) if e.kind() != ErrorKind::NotFound => {
+            Err(e).context(format!("😱 Failed to remove {:?}", path))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn copy_all(from: &Path, to: &Path) -> Result<()> {
+    if from.is_dir() {
+        fs::create_dir_all(to).context(format!("😱 Failed to create directory: {:?}", to))?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_all(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(from, to).context(format!("😱 Failed to copy {:?} to {:?}", from, to))?;
+    }
+
+    Ok(())
+}
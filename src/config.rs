@@ -8,12 +8,18 @@ use grace::GraceCompilerOptions;
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Config {
     pub modules: HashMap<String, ModuleConfig>,
+    /// How seriously to take each lint
+    ///
+    #[serde(default)]
+    pub lint: LintConfig,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ModuleConfig {
     /// Path to the model file
     ///
+    /// This may be a cuckoo JSON file, a v2 model directory, or a bincode
+    /// model file.
     pub model: PathBuf,
     /// The compiler to use for this domain
     ///
@@ -36,3 +42,51 @@ pub enum Compiler {
     //    /// AST.
     // Dwarf(DwarfOptions),
 }
+
+/// What to do when a lint finds something
+///
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    /// Don't bother checking
+    Allow,
+    /// Report it, and carry on
+    Warn,
+    /// Report it, and refuse to generate code
+    Error,
+}
+
+/// The `[lint]` table
+///
+/// Each lint has a level, and anything left out is a warning. Existing models
+/// shouldn't stop generating code just because there's a new lint.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LintConfig {
+    /// Objects without an `id` attribute
+    pub no_identifier: Level,
+    /// Object names, or attribute names within an object, that are the same
+    /// once they are snake cased
+    pub duplicate_names: Level,
+    /// Relationships without a phrase on each side
+    pub missing_phrases: Level,
+    /// Objects that aren't in any relationship
+    pub orphan_objects: Level,
+    /// Attributes named with a Rust keyword
+    pub rust_keywords: Level,
+    /// Objects without a description
+    pub empty_descriptions: Level,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        LintConfig {
+            no_identifier: Level::Warn,
+            duplicate_names: Level::Warn,
+            missing_phrases: Level::Warn,
+            orphan_objects: Level::Warn,
+            rust_keywords: Level::Warn,
+            empty_descriptions: Level::Warn,
+        }
+    }
+}
@@ -0,0 +1,580 @@
+//! Cuckoo model files
+//!
+//! Cuckoo is the modeling tool, and it writes models as JSON. Sometimes we need
+//! to poke at that JSON directly, without going through `DomainBuilder`. This is
+//! where that happens.
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
+
+use crate::view::{Anchor, AttributeType, Edge, ModelView, Rect, RelationshipView, Side};
+
+/// Where we put objects that have never been drawn
+const DEFAULT_RECT: Rect = Rect {
+    x: 0,
+    y: 0,
+    width: 200,
+    height: 100,
+};
+
+/// The namespace for a domain
+///
+/// All of a domain's UUIDs are derived from this namespace. It's written to the
+/// model's paper, as `domain_ns`, and to the generated module as `UUID_NS`, and
+/// the two had better agree.
+pub fn domain_namespace(domain: &str) -> Uuid {
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, domain.as_bytes())
+}
+
+/// Read a cuckoo model file as raw JSON
+///
+pub fn read_model<P: AsRef<Path>>(path: P) -> Result<Value> {
+    let path = path.as_ref();
+    let json = fs::read_to_string(path)
+        .context(format!("😱 unable to read model file: {}", path.display()))?;
+
+    serde_json::from_str(&json)
+        .context(format!("😱 unable to parse model file: {}", path.display()))
+}
+
+/// Render a cuckoo model in canonical form
+///
+/// Cuckoo, and whatever else touches a model, is free to write keys and `ids`
+/// in any order it likes. This puts object keys in sorted order, sorts every
+/// `ids` list, indents by two spaces, and ends with a newline. None of that
+/// changes what the model means, and a model that's been through here comes
+/// out the same every time.
+pub fn format_model(model: &Value) -> Result<String> {
+    let mut model = model.clone();
+    canonicalize(&mut model);
+
+    Ok(serde_json::to_string_pretty(&model).context("😱 unable to serialize model")? + "\n")
+}
+
+fn canonicalize(value: &mut Value) {
+    match value {
+        Value::Array(array) => {
+            for v in array {
+                canonicalize(v);
+            }
+        }
+        Value::Object(object) => {
+            let mut entries: Vec<(String, Value)> = std::mem::take(object).into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            for (key, mut v) in entries {
+                if key == "ids" {
+                    if let Value::Array(ids) = &mut v {
+                        ids.sort_by(|a, b| a.as_str().cmp(&b.as_str()));
+                    }
+                }
+                canonicalize(&mut v);
+                object.insert(key, v);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Regenerate the model's UUIDs
+///
+/// Each UUID that the model defines, as an entity key or an `id`, is replaced
+/// with a v5 UUID derived from the old one under `ns`. The mapping is
+/// consistent, so references between entities survive the trip. UUIDs that
+/// belong to some other domain, like those of imported objects, are left alone
+/// so that they still point at the right thing.
+pub fn rebase_ids(model: &mut Value, ns: &Uuid) {
+    let mut defined = HashSet::new();
+    defined_ids(model, &mut defined);
+
+    let map = defined
+        .into_iter()
+        .map(|old| (old, Uuid::new_v5(ns, old.as_bytes())))
+        .collect();
+    rebase_value(model, &map);
+}
+
+/// Collect the UUIDs that the model defines
+///
+fn defined_ids(value: &Value, defined: &mut HashSet<Uuid>) {
+    match value {
+        Value::Array(array) => {
+            for v in array {
+                defined_ids(v, defined);
+            }
+        }
+        Value::Object(object) => {
+            for (key, v) in object {
+                if let Ok(id) = Uuid::parse_str(key) {
+                    defined.insert(id);
+                }
+                if key == "id" {
+                    if let Some(id) = v.as_str().and_then(|s| Uuid::parse_str(s).ok()) {
+                        defined.insert(id);
+                    }
+                }
+                defined_ids(v, defined);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn rebase_value(value: &mut Value, map: &HashMap<Uuid, Uuid>) {
+    match value {
+        Value::String(s) => {
+            if let Some(new) = rebase_str(s, map) {
+                *s = new;
+            }
+        }
+        Value::Array(array) => {
+            for v in array {
+                rebase_value(v, map);
+            }
+        }
+        Value::Object(object) => {
+            let entries = std::mem::take(object);
+            for (key, mut v) in entries {
+                rebase_value(&mut v, map);
+                let key = rebase_str(&key, map).unwrap_or(key);
+                object.insert(key, v);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn rebase_str(s: &str, map: &HashMap<Uuid, Uuid>) -> Option<String> {
+    let old = Uuid::parse_str(s).ok()?;
+
+    map.get(&old).map(|new| new.to_string())
+}
+
+/// Parse a cuckoo model from a string
+///
+pub fn parse_model(json: &str) -> Result<Value> {
+    serde_json::from_str(json).context("😱 unable to parse model")
+}
+
+/// Fetch the namespace from the model's paper
+///
+pub fn paper_namespace(model: &Value) -> Option<Uuid> {
+    model
+        .pointer("/paper/entities")?
+        .as_object()?
+        .values()
+        .find_map(|paper| paper.get("domain_ns")?.as_str())
+        .and_then(|ns| Uuid::parse_str(ns).ok())
+}
+
+/// Set the domain name and namespace on the model's paper
+///
+/// There is (as far as cuckoo is concerned) only ever one paper, but it's
+/// stored like every other entity, so we just update them all.
+pub fn set_paper(model: &mut Value, domain_name: &str, domain_ns: &Uuid) {
+    if let Some(entities) = model
+        .pointer_mut("/paper/entities")
+        .and_then(|e| e.as_object_mut())
+    {
+        for paper in entities.values_mut() {
+            if let Some(paper) = paper.as_object_mut() {
+                paper.insert("domain_name".to_owned(), Value::from(domain_name));
+                paper.insert("domain_ns".to_owned(), Value::from(domain_ns.to_string()));
+            }
+        }
+    }
+}
+
+/// Fill in a model view from a cuckoo model's paper
+///
+/// The v2 model doesn't know how far the paper was scrolled, or where each
+/// relationship was drawn, but the cuckoo model does. Anything the paper
+/// doesn't have is left alone.
+pub fn read_paper(model: &Value, view: &mut ModelView) {
+    let paper = match model
+        .pointer("/paper/entities")
+        .and_then(|e| e.as_object())
+        .and_then(|e| e.values().next())
+    {
+        Some(paper) => paper,
+        None => return,
+    };
+
+    if let Some(ns) = paper_namespace(model) {
+        view.domain_ns = ns;
+    }
+    if let Some(offset) = paper.get("offset").and_then(read_point) {
+        view.offset = offset;
+    }
+
+    let relationships = match paper.get("relationships").and_then(|r| r.as_object()) {
+        Some(relationships) => relationships,
+        None => return,
+    };
+    for (id, ui) in relationships {
+        let id = match Uuid::parse_str(id) {
+            Ok(id) => id,
+            Err(_) => continue,
+        };
+        let ends: Vec<&Value> = if let Some(ui) = ui.get("BinaryUI") {
+            vec![&ui["from"], &ui["to"]]
+        } else if let Some(ui) = ui.get("IsaUI") {
+            // Each subtype anchor carries its object's id, and that's how we
+            // put them in the view's order.
+            let isa = match view.relationships.iter().find(|r| r.id() == id) {
+                Some(RelationshipView::Isa(isa)) => isa,
+                _ => continue,
+            };
+            let to = ui["to"]
+                .as_array()
+                .map(|a| a.as_slice())
+                .unwrap_or_default();
+            let subtypes = isa.subtypes.iter().map(|sub| {
+                to.iter()
+                    .find(|a| a["id"].as_str() == Some(&sub.to_string()))
+                    .unwrap_or(&Value::Null)
+            });
+            std::iter::once(&ui["from"]).chain(subtypes).collect()
+        } else if let Some(ui) = ui.get("AssociativeUI") {
+            vec![&ui["from"], &ui["one"], &ui["other"]]
+        } else {
+            continue;
+        };
+
+        if let Some(anchors) = ends.into_iter().map(read_anchor).collect() {
+            view.anchors.insert(id, anchors);
+        }
+    }
+}
+
+fn read_anchor(anchor: &Value) -> Option<Anchor> {
+    let (x, y) = read_point(anchor)?;
+    Some(Anchor {
+        edge: Edge::from_dir(anchor.get("dir")?.as_str()?)?,
+        x,
+        y,
+        offset: read_point(anchor.get("offset")?)?,
+    })
+}
+
+fn read_point(point: &Value) -> Option<(i64, i64)> {
+    Some((point.get("x")?.as_i64()?, point.get("y")?.as_i64()?))
+}
+
+/// Write a model view as a cuckoo model
+///
+/// This is the way back from a v2 model. Relationships are drawn where the view
+/// says they were. Any that it doesn't know about are anchored to the middle of
+/// the top edge of each object; cuckoo will sort it out when someone moves
+/// something.
+pub fn from_view(view: &ModelView) -> Value {
+    let paper_id = Uuid::new_v5(&view.domain_ns, b"paper");
+
+    let mut objects = Map::new();
+    let mut paper_objects = Map::new();
+    for object in &view.objects {
+        let mut attributes = Map::new();
+        for attr in &object.attributes {
+            let ty = match &attr.ty {
+                AttributeType::Boolean => json!("Boolean"),
+                AttributeType::Float => json!("Float"),
+                AttributeType::Integer => json!("Integer"),
+                AttributeType::String => json!("String"),
+                AttributeType::Uuid => json!("Uuid"),
+                AttributeType::Object(id) => json!({ "Object": id.to_string() }),
+                AttributeType::External(name) => json!({ "External": name }),
+            };
+            attributes.insert(
+                attr.id.to_string(),
+                json!({
+                    "id": attr.id.to_string(),
+                    "name": attr.name,
+                    "type": ty,
+                }),
+            );
+        }
+
+        objects.insert(
+            object.id.to_string(),
+            json!({
+                "id": object.id.to_string(),
+                "key_letter": object.key_letters,
+                "name": object.name,
+                "description": object.description,
+                "attributes": attributes,
+            }),
+        );
+
+        let rect = object.layout.unwrap_or(DEFAULT_RECT);
+        paper_objects.insert(
+            object.id.to_string(),
+            json!({
+                "id": object.id.to_string(),
+                "x": rect.x,
+                "y": rect.y,
+                "width": rect.width,
+                "height": rect.height,
+            }),
+        );
+    }
+
+    // The stored anchor if there is one, and otherwise the top of the object.
+    let anchor = |rel: &Uuid, index: usize, id: &Uuid| {
+        let anchor = view
+            .anchors
+            .get(rel)
+            .and_then(|a| a.get(index))
+            .copied()
+            .unwrap_or_else(|| {
+                let rect = view
+                    .object(id)
+                    .and_then(|o| o.layout)
+                    .unwrap_or(DEFAULT_RECT);
+                Anchor {
+                    edge: Edge::Top,
+                    x: rect.x + rect.width / 2,
+                    y: rect.y,
+                    offset: (0, 0),
+                }
+            });
+        json!({
+            "id": id.to_string(),
+            "x": anchor.x,
+            "y": anchor.y,
+            "offset": { "x": anchor.offset.0, "y": anchor.offset.1 },
+            "dir": anchor.edge.as_str(),
+        })
+    };
+
+    let side = |side: &Side| {
+        json!({
+            "obj_id": side.obj_id.to_string(),
+            "description": side.description,
+            "cardinality": side.cardinality.as_str(),
+            "conditionality": side.conditionality.as_str(),
+        })
+    };
+
+    let mut relationships = Map::new();
+    let mut paper_relationships = Map::new();
+    for rel in &view.relationships {
+        let (entity, ui) = match rel {
+            RelationshipView::Binary(binary) => {
+                let mut from = side(&binary.from);
+                from["formalizing_attribute_name"] = json!(binary.referential_attribute);
+                (
+                    json!({ "Binary": {
+                        "id": binary.id.to_string(),
+                        "number": binary.number,
+                        "from": from,
+                        "to": side(&binary.to),
+                    }}),
+                    json!({ "BinaryUI": {
+                        "from": anchor(&binary.id, 0, &binary.from.obj_id),
+                        "to": anchor(&binary.id, 1, &binary.to.obj_id),
+                    }}),
+                )
+            }
+            RelationshipView::Isa(isa) => (
+                json!({ "Isa": {
+                    "id": isa.id.to_string(),
+                    "number": isa.number,
+                    "obj_id": isa.supertype.to_string(),
+                    "subtypes": isa.subtypes.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+                }}),
+                json!({ "IsaUI": {
+                    "from": anchor(&isa.id, 0, &isa.supertype),
+                    "to": isa
+                        .subtypes
+                        .iter()
+                        .enumerate()
+                        .map(|(i, s)| anchor(&isa.id, i + 1, s))
+                        .collect::<Vec<_>>(),
+                }}),
+            ),
+            RelationshipView::Associative(assoc) => (
+                json!({ "Associative": {
+                    "id": assoc.id.to_string(),
+                    "number": assoc.number,
+                    "from": {
+                        "obj_id": assoc.from.to_string(),
+                        "one_referential_attribute": assoc.one_referential_attribute,
+                        "other_referential_attribute": assoc.other_referential_attribute,
+                    },
+                    "one": side(&assoc.one),
+                    "other": side(&assoc.other),
+                }}),
+                json!({ "AssociativeUI": {
+                    "from": anchor(&assoc.id, 0, &assoc.from),
+                    "one": anchor(&assoc.id, 1, &assoc.one.obj_id),
+                    "other": anchor(&assoc.id, 2, &assoc.other.obj_id),
+                }}),
+            ),
+        };
+
+        relationships.insert(rel.id().to_string(), entity);
+        paper_relationships.insert(rel.id().to_string(), ui);
+    }
+
+    let ids = |map: &Map<String, Value>| map.keys().cloned().collect::<Vec<_>>();
+
+    json!({
+        "paper": {
+            "ids": [paper_id.to_string()],
+            "entities": {
+                paper_id.to_string(): {
+                    "id": paper_id.to_string(),
+                    "domain_name": view.name,
+                    "description": view.description,
+                    "domain_ns": view.domain_ns.to_string(),
+                    "width": view.extents.0,
+                    "height": view.extents.1,
+                    "offset": { "x": view.offset.0, "y": view.offset.1 },
+                    "objects": paper_objects,
+                    "relationships": paper_relationships,
+                }
+            }
+        },
+        "objects": {
+            "ids": ids(&objects),
+            "entities": objects,
+        },
+        "relationships": {
+            "ids": ids(&relationships),
+            "entities": relationships,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::{
+        diff::diff_conversion,
+        model::Format,
+        view::{IsaView, ObjectView},
+    };
+
+    const SUPER: Uuid = Uuid::from_u128(0x5);
+    const SUB_A: Uuid = Uuid::from_u128(0xa);
+    const SUB_B: Uuid = Uuid::from_u128(0xb);
+    const R1: Uuid = Uuid::from_u128(0x1);
+
+    fn object(id: Uuid, name: &str, x: i64, y: i64) -> ObjectView {
+        ObjectView {
+            id,
+            name: name.to_owned(),
+            key_letters: name.to_lowercase(),
+            description: String::new(),
+            attributes: Vec::new(),
+            layout: Some(Rect {
+                x,
+                y,
+                width: 100,
+                height: 50,
+            }),
+        }
+    }
+
+    fn anchor(edge: Edge, x: i64, y: i64) -> Anchor {
+        Anchor {
+            edge,
+            x,
+            y,
+            offset: (5, -5),
+        }
+    }
+
+    /// A supertype at the top, and two subtypes underneath it
+    ///
+    fn model() -> ModelView {
+        ModelView {
+            name: "shapes".to_owned(),
+            description: String::new(),
+            domain_ns: domain_namespace("shapes"),
+            extents: (1000, 800),
+            offset: (40, -25),
+            objects: vec![
+                object(SUPER, "Shape", 200, 0),
+                object(SUB_B, "Square", 0, 200),
+                object(SUB_A, "Circle", 400, 200),
+            ],
+            relationships: vec![RelationshipView::Isa(IsaView {
+                id: R1,
+                number: 1,
+                supertype: SUPER,
+                subtypes: vec![SUB_A, SUB_B],
+            })],
+            anchors: BTreeMap::from([(
+                R1,
+                vec![
+                    anchor(Edge::Bottom, 250, 50),
+                    anchor(Edge::Top, 450, 200),
+                    anchor(Edge::Top, 50, 200),
+                ],
+            )]),
+        }
+    }
+
+    /// Write a view as a cuckoo model, and read it back
+    ///
+    fn round_trip(view: &ModelView, edit: impl FnOnce(&mut Value)) -> ModelView {
+        let mut model = parse_model(&format_model(&from_view(view)).unwrap()).unwrap();
+        edit(&mut model);
+
+        let mut back = ModelView {
+            offset: (0, 0),
+            anchors: BTreeMap::new(),
+            ..view.clone()
+        };
+        read_paper(&model, &mut back);
+        back
+    }
+
+    #[test]
+    fn paper_offset_survives_a_trip_through_a_directory() {
+        let cuckoo = model();
+        // A v2 model has no paper, so it comes back without the offset.
+        let dir = ModelView {
+            offset: (0, 0),
+            ..cuckoo.clone()
+        };
+
+        assert!(diff_conversion(&cuckoo, &dir, Format::Dir).is_empty());
+        assert!(!diff_conversion(&cuckoo, &dir, Format::Cuckoo).is_empty());
+
+        let back = round_trip(&dir, |_| ());
+        assert!(diff_conversion(&dir, &back, Format::Cuckoo).is_empty());
+        assert_eq!(back, dir);
+    }
+
+    #[test]
+    fn subtype_anchors_follow_their_objects() {
+        let view = model();
+
+        // Cuckoo is free to list the subtypes in any order.
+        let back = round_trip(&view, |model| {
+            let paper = model["paper"]["entities"]
+                .as_object_mut()
+                .unwrap()
+                .values_mut()
+                .next()
+                .unwrap();
+            let to = paper["relationships"][R1.to_string()]["IsaUI"]["to"]
+                .as_array_mut()
+                .unwrap();
+            to.reverse();
+            assert_eq!(to[0]["id"], SUB_B.to_string());
+        });
+
+        assert_eq!(back.anchors, view.anchors);
+        assert!(diff_conversion(&view, &back, Format::Cuckoo).is_empty());
+    }
+}
@@ -0,0 +1,286 @@
+//! Semantic model differences
+//!
+//! Two models are compared by name, rather than by UUID or by the bytes in the
+//! file. Objects are matched by name, attributes by name within their object,
+//! and relationships by number. Layout changes are reported separately, since
+//! they don't change what the model means.
+use std::{collections::BTreeMap, fmt};
+
+use ansi_term::Colour;
+
+use crate::{
+    model::Format,
+    view::{AttributeView, ModelView, ObjectView, RelationshipView, Side},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+    /// Only the layout changed
+    Moved,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    pub kind: ChangeKind,
+    /// What changed, e.g., "object `Foo`"
+    pub subject: String,
+    /// How it changed, if there's more to say
+    pub detail: Option<String>,
+}
+
+impl Change {
+    fn new<S: Into<String>>(kind: ChangeKind, subject: S) -> Self {
+        Change {
+            kind,
+            subject: subject.into(),
+            detail: None,
+        }
+    }
+
+    fn changed<S: Into<String>, T: fmt::Display>(subject: S, what: &str, was: T, now: T) -> Self {
+        Change {
+            kind: ChangeKind::Changed,
+            subject: subject.into(),
+            detail: Some(format!("{}: {} ➡️  {}", what, was, now)),
+        }
+    }
+
+    pub fn is_layout(&self) -> bool {
+        self.kind == ChangeKind::Moved
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (sigil, colour) = match self.kind {
+            ChangeKind::Added => ("+", Colour::Green),
+            ChangeKind::Removed => ("-", Colour::Red),
+            ChangeKind::Changed => ("~", Colour::Yellow),
+            ChangeKind::Moved => ("↔", Colour::Blue),
+        };
+
+        write!(f, "{} {}", colour.paint(sigil), self.subject)?;
+        if let Some(detail) = &self.detail {
+            write!(f, " ({})", detail)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compare two models
+///
+/// The changes take you from `a` to `b`.
+pub fn diff_views(a: &ModelView, b: &ModelView) -> Vec<Change> {
+    let mut changes = Vec::new();
+
+    if a.name != b.name {
+        changes.push(Change::changed("domain", "name", &a.name, &b.name));
+    }
+    if a.description != b.description {
+        changes.push(Change::new(ChangeKind::Changed, "domain description"));
+    }
+    if a.domain_ns != b.domain_ns {
+        changes.push(Change::changed(
+            "domain",
+            "namespace",
+            &a.domain_ns,
+            &b.domain_ns,
+        ));
+    }
+    if a.extents != b.extents {
+        changes.push(Change {
+            kind: ChangeKind::Moved,
+            subject: "paper".to_owned(),
+            detail: Some(format!(
+                "{}x{} ➡️  {}x{}",
+                a.extents.0, a.extents.1, b.extents.0, b.extents.1
+            )),
+        });
+    }
+    if a.offset != b.offset {
+        changes.push(Change {
+            kind: ChangeKind::Moved,
+            subject: "paper".to_owned(),
+            detail: Some(format!(
+                "offset {},{} ➡️  {},{}",
+                a.offset.0, a.offset.1, b.offset.0, b.offset.1
+            )),
+        });
+    }
+
+    let a_objects: BTreeMap<_, _> = a.objects.iter().map(|o| (&o.name, o)).collect();
+    let b_objects: BTreeMap<_, _> = b.objects.iter().map(|o| (&o.name, o)).collect();
+
+    for (name, object) in &a_objects {
+        match b_objects.get(name) {
+            Some(other) => diff_objects(object, other, a, b, &mut changes),
+            None => changes.push(Change::new(
+                ChangeKind::Removed,
+                format!("object `{}`", name),
+            )),
+        }
+    }
+    for name in b_objects.keys() {
+        if !a_objects.contains_key(name) {
+            changes.push(Change::new(ChangeKind::Added, format!("object `{}`", name)));
+        }
+    }
+
+    let a_rels: BTreeMap<_, _> = a.relationships.iter().map(|r| (r.number(), r)).collect();
+    let b_rels: BTreeMap<_, _> = b.relationships.iter().map(|r| (r.number(), r)).collect();
+
+    for (number, rel) in &a_rels {
+        let was = describe_relationship(a, rel);
+        match b_rels.get(number) {
+            Some(other) => {
+                let now = describe_relationship(b, other);
+                if was != now {
+                    changes.push(Change::changed(rel.name(), "was", was, now));
+                }
+                if a.anchors.get(&rel.id()) != b.anchors.get(&other.id()) {
+                    changes.push(Change {
+                        kind: ChangeKind::Moved,
+                        subject: rel.name(),
+                        detail: Some("anchors".to_owned()),
+                    });
+                }
+            }
+            None => changes.push(Change {
+                kind: ChangeKind::Removed,
+                subject: rel.name(),
+                detail: Some(was),
+            }),
+        }
+    }
+    for (number, rel) in &b_rels {
+        if !a_rels.contains_key(number) {
+            changes.push(Change {
+                kind: ChangeKind::Added,
+                subject: rel.name(),
+                detail: Some(describe_relationship(b, rel)),
+            });
+        }
+    }
+
+    changes
+}
+
+/// Compare a model with what it became after being written as `format`
+///
+/// Only cuckoo models have a paper to scroll, so a model written in any other
+/// format is only held to what it means, and not to how it's drawn.
+pub fn diff_conversion(before: &ModelView, after: &ModelView, format: Format) -> Vec<Change> {
+    diff_views(before, after)
+        .into_iter()
+        .filter(|c| format == Format::Cuckoo || !c.is_layout())
+        .collect()
+}
+
+fn diff_objects(
+    a: &ObjectView,
+    b: &ObjectView,
+    a_view: &ModelView,
+    b_view: &ModelView,
+    changes: &mut Vec<Change>,
+) {
+    let subject = format!("object `{}`", a.name);
+
+    if a.key_letters != b.key_letters {
+        changes.push(Change::changed(
+            &subject,
+            "key letters",
+            &a.key_letters,
+            &b.key_letters,
+        ));
+    }
+    if a.description != b.description {
+        changes.push(Change {
+            kind: ChangeKind::Changed,
+            subject: subject.clone(),
+            detail: Some("description".to_owned()),
+        });
+    }
+    if a.layout != b.layout {
+        let position = |o: &ObjectView| {
+            o.layout
+                .map(|r| format!("({}, {}) {}x{}", r.x, r.y, r.width, r.height))
+                .unwrap_or_else(|| "nowhere".to_owned())
+        };
+        changes.push(Change {
+            kind: ChangeKind::Moved,
+            subject: subject.clone(),
+            detail: Some(format!("{} ➡️  {}", position(a), position(b))),
+        });
+    }
+
+    let a_attrs: BTreeMap<_, _> = a.attributes.iter().map(|a| (&a.name, a)).collect();
+    let b_attrs: BTreeMap<_, _> = b.attributes.iter().map(|a| (&a.name, a)).collect();
+
+    let attr_subject = |attr: &AttributeView| format!("attribute `{}.{}`", a.name, attr.name);
+
+    for (name, attr) in &a_attrs {
+        match b_attrs.get(name) {
+            Some(other) => {
+                let was = attr.ty.name(a_view);
+                let now = other.ty.name(b_view);
+                if was != now {
+                    changes.push(Change::changed(attr_subject(attr), "type", was, now));
+                }
+            }
+            None => changes.push(Change::new(ChangeKind::Removed, attr_subject(attr))),
+        }
+    }
+    for (name, attr) in &b_attrs {
+        if !a_attrs.contains_key(name) {
+            changes.push(Change::new(ChangeKind::Added, attr_subject(attr)));
+        }
+    }
+}
+
+/// Describe a relationship using object names
+///
+/// Two relationships with the same description are, for our purposes, the same.
+pub fn describe_relationship(view: &ModelView, rel: &RelationshipView) -> String {
+    let side = |side: &Side| {
+        format!(
+            "{} {} `{}` {}",
+            side.conditionality.as_str().to_lowercase(),
+            side.cardinality.as_str().to_lowercase(),
+            side.description,
+            view.object_name(&side.obj_id)
+        )
+    };
+
+    match rel {
+        RelationshipView::Binary(binary) => format!(
+            "{} [{}] -> {} / {} -> {}",
+            view.object_name(&binary.from.obj_id),
+            binary.referential_attribute,
+            side(&binary.to),
+            view.object_name(&binary.to.obj_id),
+            side(&binary.from)
+        ),
+        RelationshipView::Isa(isa) => {
+            let mut subtypes: Vec<String> =
+                isa.subtypes.iter().map(|s| view.object_name(s)).collect();
+            subtypes.sort();
+            format!(
+                "{} is a supertype of {}",
+                view.object_name(&isa.supertype),
+                subtypes.join(", ")
+            )
+        }
+        RelationshipView::Associative(assoc) => format!(
+            "{} [{}, {}] associates {} and {}",
+            view.object_name(&assoc.from),
+            assoc.one_referential_attribute,
+            assoc.other_referential_attribute,
+            side(&assoc.one),
+            side(&assoc.other)
+        ),
+    }
+}
@@ -0,0 +1,489 @@
+//! Domain documentation
+//!
+//! A small documentation site for a domain: an index page with the diagram and
+//! a list of objects, and a page for each object. Object pages list the
+//! attributes, and the relationships that the object takes part in, read as
+//! sentences. Anywhere another object is mentioned, it's linked to that
+//! object's page.
+//!
+//! The site is written as either HTML or Markdown. Nothing is written to disk
+//! here; we just hand back the pages.
+//!
+//! Object pages go in their own directory, so that an object can't be named
+//! over the index. Objects whose names come out the same once snake cased get
+//! a number on the end.
+use std::collections::{HashMap, HashSet};
+
+use heck::ToSnakeCase;
+use uuid::Uuid;
+
+use crate::{
+    export::{escape_xml, one_line},
+    render,
+    view::{
+        AttributeType, Cardinality, Conditionality, ModelView, ObjectView, RelationshipView, Side,
+    },
+};
+
+const INDEX: &str = "index";
+const DIAGRAM: &str = "diagram.svg";
+/// Where the object pages go, relative to the root of the site
+const OBJECT_DIR: &str = "objects";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Html,
+    Markdown,
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Html => "html",
+            Format::Markdown => "md",
+        }
+    }
+}
+
+/// A file in the documentation site
+///
+#[derive(Clone, Debug)]
+pub struct Page {
+    /// The file name, relative to the root of the site
+    pub file_name: String,
+    pub contents: String,
+}
+
+/// Document a domain
+///
+pub fn document(view: &ModelView, format: Format) -> Vec<Page> {
+    let stems = object_stems(view);
+    let index = Markup {
+        format,
+        root: "",
+        stems: &stems,
+    };
+    let markup = Markup {
+        format,
+        root: "../",
+        stems: &stems,
+    };
+
+    let mut pages = vec![
+        Page {
+            file_name: page_name(INDEX, format),
+            contents: index_page(view, &index),
+        },
+        Page {
+            file_name: DIAGRAM.to_owned(),
+            contents: render::to_svg(view),
+        },
+    ];
+
+    for object in &view.objects {
+        pages.push(Page {
+            file_name: format!("{}/{}", OBJECT_DIR, page_name(&stems[&object.id], format)),
+            contents: object_page(view, object, &markup),
+        });
+    }
+
+    pages
+}
+
+fn index_page(view: &ModelView, markup: &Markup) -> String {
+    let mut body = String::new();
+
+    body += &markup.heading(1, &markup.text(&view.name));
+    body += &markup.description(&view.description);
+    body += &markup.image(DIAGRAM, &format!("{} diagram", view.name));
+
+    body += &markup.heading(2, "Objects");
+    let rows: Vec<Vec<String>> = view
+        .objects
+        .iter()
+        .map(|object| {
+            vec![
+                markup.object_link(object),
+                markup.text(&object.key_letters),
+                markup.text(&summary(&object.description)),
+            ]
+        })
+        .collect();
+    body += &markup.table(&["Object", "Key Letters", "Description"], &rows);
+
+    if !view.relationships.is_empty() {
+        body += &markup.heading(2, "Relationships");
+        let items: Vec<String> = view
+            .relationships
+            .iter()
+            .map(|rel| relationship_summary(view, rel, markup))
+            .collect();
+        body += &markup.list(&items);
+    }
+
+    markup.page(&view.name, &body)
+}
+
+fn object_page(view: &ModelView, object: &ObjectView, markup: &Markup) -> String {
+    let mut body = String::new();
+
+    body += &markup.paragraph(&markup.link(&markup.text(&view.name), INDEX));
+    body += &markup.heading(
+        1,
+        &markup.text(&format!("{} ({})", object.name, object.key_letters)),
+    );
+    body += &markup.description(&object.description);
+
+    body += &markup.heading(2, "Attributes");
+    let rows: Vec<Vec<String>> = object
+        .attributes
+        .iter()
+        .map(|attr| {
+            let ty = match &attr.ty {
+                AttributeType::Object(id) => markup.object_link_by_id(view, id),
+                ty => markup.text(&ty.name(view)),
+            };
+            vec![markup.text(&attr.name), ty]
+        })
+        .collect();
+    body += &markup.table(&["Name", "Type"], &rows);
+
+    let relationships = view.relationships_of(&object.id);
+    if !relationships.is_empty() {
+        body += &markup.heading(2, "Relationships");
+        let items: Vec<String> = relationships
+            .iter()
+            .flat_map(|rel| relationship_sentences(view, object, rel, markup))
+            .collect();
+        body += &markup.list(&items);
+    }
+
+    markup.page(&object.name, &body)
+}
+
+/// Read a relationship from the point of view of an object
+///
+/// A reflexive relationship reads both ways, so there may be more than one
+/// sentence.
+fn relationship_sentences(
+    view: &ModelView,
+    object: &ObjectView,
+    rel: &RelationshipView,
+    markup: &Markup,
+) -> Vec<String> {
+    let name = markup.strong(&rel.name());
+    let this = markup.text(&object.name);
+    let mut sentences = Vec::new();
+
+    match rel {
+        RelationshipView::Binary(binary) => {
+            if binary.from.obj_id == object.id {
+                sentences.push(format!(
+                    "{}: {} {}",
+                    name,
+                    this,
+                    read_side(view, &binary.to, markup)
+                ));
+            }
+            if binary.to.obj_id == object.id {
+                sentences.push(format!(
+                    "{}: {} {}",
+                    name,
+                    this,
+                    read_side(view, &binary.from, markup)
+                ));
+            }
+        }
+        RelationshipView::Isa(isa) => {
+            if isa.supertype == object.id {
+                let subtypes: Vec<String> = isa
+                    .subtypes
+                    .iter()
+                    .map(|s| markup.object_link_by_id(view, s))
+                    .collect();
+                sentences.push(format!(
+                    "{}: {} is a supertype of {}",
+                    name,
+                    this,
+                    subtypes.join(", ")
+                ));
+            }
+            if isa.subtypes.contains(&object.id) {
+                sentences.push(format!(
+                    "{}: {} is a subtype of {}",
+                    name,
+                    this,
+                    markup.object_link_by_id(view, &isa.supertype)
+                ));
+            }
+        }
+        RelationshipView::Associative(assoc) => {
+            if assoc.from == object.id {
+                sentences.push(format!(
+                    "{}: {} formalizes the association between {} and {}",
+                    name,
+                    this,
+                    markup.object_link_by_id(view, &assoc.one.obj_id),
+                    markup.object_link_by_id(view, &assoc.other.obj_id)
+                ));
+            }
+            let formalizer = markup.object_link_by_id(view, &assoc.from);
+            if assoc.one.obj_id == object.id {
+                sentences.push(format!(
+                    "{}: {} {}, formalized by {}",
+                    name,
+                    this,
+                    read_side(view, &assoc.other, markup),
+                    formalizer
+                ));
+            }
+            if assoc.other.obj_id == object.id {
+                sentences.push(format!(
+                    "{}: {} {}, formalized by {}",
+                    name,
+                    this,
+                    read_side(view, &assoc.one, markup),
+                    formalizer
+                ));
+            }
+        }
+    }
+
+    sentences
+}
+
+/// Read the far end of a relationship, e.g., "owns zero or more Widget"
+///
+fn read_side(view: &ModelView, side: &Side, markup: &Markup) -> String {
+    let phrase = one_line(&side.description);
+    let quantity = match (side.cardinality, side.conditionality) {
+        (Cardinality::One, Conditionality::Unconditional) => "exactly one",
+        (Cardinality::One, Conditionality::Conditional) => "zero or one",
+        (Cardinality::Many, Conditionality::Unconditional) => "one or more",
+        (Cardinality::Many, Conditionality::Conditional) => "zero or more",
+    };
+    let other = markup.object_link_by_id(view, &side.obj_id);
+
+    if phrase.is_empty() {
+        format!("is related to {} {}", quantity, other)
+    } else {
+        format!("{} {} {}", markup.text(&phrase), quantity, other)
+    }
+}
+
+fn relationship_summary(view: &ModelView, rel: &RelationshipView, markup: &Markup) -> String {
+    let objects: Vec<String> = rel
+        .objects()
+        .iter()
+        .map(|id| markup.object_link_by_id(view, id))
+        .collect();
+
+    format!("{}: {}", markup.strong(&rel.name()), objects.join(", "))
+}
+
+/// The first paragraph of a description
+///
+fn summary(description: &str) -> String {
+    one_line(description.split("\n\n").next().unwrap_or_default())
+}
+
+/// The file name, less the extension, of each object's page
+///
+fn object_stems(view: &ModelView) -> HashMap<Uuid, String> {
+    let mut used = HashSet::new();
+    let mut stems = HashMap::new();
+
+    for object in &view.objects {
+        let base = match object.name.to_snake_case() {
+            stem if stem.is_empty() => "object".to_owned(),
+            stem => stem,
+        };
+
+        let mut stem = base.clone();
+        let mut n = 2;
+        while !used.insert(stem.clone()) {
+            stem = format!("{}_{}", base, n);
+            n += 1;
+        }
+        stems.insert(object.id, stem);
+    }
+
+    stems
+}
+
+fn page_name(stem: &str, format: Format) -> String {
+    format!("{}.{}", stem, format.extension())
+}
+
+/// Just enough markup for our purposes
+///
+/// Everything that takes markup expects it to be escaped already, courtesy of
+/// `text`.
+struct Markup<'a> {
+    format: Format,
+    /// The way back to the root of the site from the page being written
+    root: &'static str,
+    stems: &'a HashMap<Uuid, String>,
+}
+
+impl<'a> Markup<'a> {
+    fn text(&self, s: &str) -> String {
+        match self.format {
+            Format::Html => escape_xml(s),
+            Format::Markdown => s.replace('|', "\\|"),
+        }
+    }
+
+    fn strong(&self, s: &str) -> String {
+        match self.format {
+            Format::Html => format!("<strong>{}</strong>", escape_xml(s)),
+            Format::Markdown => format!("**{}**", s),
+        }
+    }
+
+    /// Link to a page, by its path from the root of the site
+    ///
+    fn link(&self, markup: &str, stem: &str) -> String {
+        let href = format!("{}{}", self.root, page_name(stem, self.format));
+        match self.format {
+            Format::Html => format!("<a href=\"{}\">{}</a>", href, markup),
+            Format::Markdown => format!("[{}]({})", markup, href),
+        }
+    }
+
+    fn object_link(&self, object: &ObjectView) -> String {
+        let stem = format!("{}/{}", OBJECT_DIR, self.stems[&object.id]);
+        self.link(&self.text(&object.name), &stem)
+    }
+
+    fn object_link_by_id(&self, view: &ModelView, id: &Uuid) -> String {
+        match view.object(id) {
+            Some(object) => self.object_link(object),
+            None => self.text(&id.to_string()),
+        }
+    }
+
+    fn heading(&self, level: usize, markup: &str) -> String {
+        match self.format {
+            Format::Html => format!("<h{l}>{}</h{l}>\n", markup, l = level),
+            Format::Markdown => format!("{} {}\n\n", "#".repeat(level), markup),
+        }
+    }
+
+    fn paragraph(&self, markup: &str) -> String {
+        match self.format {
+            Format::Html => format!("<p>{}</p>\n", markup),
+            Format::Markdown => format!("{}\n\n", markup),
+        }
+    }
+
+    /// A description, which may run to several paragraphs
+    ///
+    fn description(&self, description: &str) -> String {
+        let paragraphs: Vec<&str> = description
+            .split("\n\n")
+            .map(|p| p.trim())
+            .filter(|p| !p.is_empty())
+            .collect();
+
+        if paragraphs.is_empty() {
+            return self.paragraph(&self.emphasis("No description."));
+        }
+
+        // Markdown descriptions are passed through as they are, since they are
+        // quite likely Markdown already.
+        paragraphs
+            .iter()
+            .map(|p| match self.format {
+                Format::Html => self.paragraph(&escape_xml(p)),
+                Format::Markdown => self.paragraph(p),
+            })
+            .collect()
+    }
+
+    fn emphasis(&self, s: &str) -> String {
+        match self.format {
+            Format::Html => format!("<em>{}</em>", escape_xml(s)),
+            Format::Markdown => format!("_{}_", s),
+        }
+    }
+
+    fn image(&self, src: &str, alt: &str) -> String {
+        match self.format {
+            Format::Html => format!("<p><img src=\"{}\" alt=\"{}\"></p>\n", src, escape_xml(alt)),
+            Format::Markdown => format!("![{}]({})\n\n", alt, src),
+        }
+    }
+
+    fn list(&self, items: &[String]) -> String {
+        match self.format {
+            Format::Html => {
+                let items: String = items
+                    .iter()
+                    .map(|item| format!("  <li>{}</li>\n", item))
+                    .collect();
+                format!("<ul>\n{}</ul>\n", items)
+            }
+            Format::Markdown => {
+                let items: String = items.iter().map(|item| format!("- {}\n", item)).collect();
+                format!("{}\n", items)
+            }
+        }
+    }
+
+    fn table(&self, headers: &[&str], rows: &[Vec<String>]) -> String {
+        match self.format {
+            Format::Html => {
+                let mut table = String::from("<table>\n  <tr>");
+                for header in headers {
+                    table += &format!("<th>{}</th>", escape_xml(header));
+                }
+                table += "</tr>\n";
+                for row in rows {
+                    table += "  <tr>";
+                    for cell in row {
+                        table += &format!("<td>{}</td>", cell);
+                    }
+                    table += "</tr>\n";
+                }
+                table += "</table>\n";
+                table
+            }
+            Format::Markdown => {
+                let mut table = format!("| {} |\n", headers.join(" | "));
+                table += &format!("|{}\n", " --- |".repeat(headers.len()));
+                for row in rows {
+                    table += &format!("| {} |\n", row.join(" | "));
+                }
+                table += "\n";
+                table
+            }
+        }
+    }
+
+    fn page(&self, title: &str, body: &str) -> String {
+        match self.format {
+            Format::Html => format!(
+                r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{}</title>
+<style>
+  body {{ font-family: Helvetica, Arial, sans-serif; max-width: 60em; margin: 2em auto; }}
+  table {{ border-collapse: collapse; }}
+  th, td {{ border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; }}
+  img {{ max-width: 100%; }}
+</style>
+</head>
+<body>
+{}</body>
+</html>
+"#,
+                escape_xml(title),
+                body
+            ),
+            Format::Markdown => body.trim_end().to_owned() + "\n",
+        }
+    }
+}
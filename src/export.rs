@@ -0,0 +1,165 @@
+//! Model exporters
+//!
+//! Each exporter takes a [`ModelView`] and turns it into text in some other
+//! format. They don't touch the file system; that's up to the caller.
+//!
+//! [`ModelView`]: crate::view::ModelView
+use std::collections::{HashMap, HashSet};
+
+use heck::ToUpperCamelCase;
+use uuid::Uuid;
+
+use crate::view::{Cardinality, Conditionality, ModelView, RelationshipView, Side};
+
+pub mod dot;
+pub mod json_schema;
+pub mod mermaid;
+pub mod plantuml;
+pub mod sql;
+
+/// The multiplicity of one end of a relationship
+///
+/// This is the Shlaer-Mellor notation: `1` or `M`, with a `c` when it's
+/// conditional.
+pub fn multiplicity(side: &Side) -> &'static str {
+    match (side.cardinality, side.conditionality) {
+        (Cardinality::One, Conditionality::Unconditional) => "1",
+        (Cardinality::One, Conditionality::Conditional) => "1c",
+        (Cardinality::Many, Conditionality::Unconditional) => "M",
+        (Cardinality::Many, Conditionality::Conditional) => "Mc",
+    }
+}
+
+/// The multiplicity of one end of a relationship, as UML would have it
+///
+pub fn uml_multiplicity(side: &Side) -> &'static str {
+    match (side.cardinality, side.conditionality) {
+        (Cardinality::One, Conditionality::Unconditional) => "1",
+        (Cardinality::One, Conditionality::Conditional) => "0..1",
+        (Cardinality::Many, Conditionality::Unconditional) => "1..*",
+        (Cardinality::Many, Conditionality::Conditional) => "0..*",
+    }
+}
+
+/// Identifiers for the objects, for formats that are picky about such things
+///
+/// Names that differ only in case or punctuation, like `Foo Bar` and `foo_bar`,
+/// come out the same, so the later ones are numbered.
+pub fn class_names(view: &ModelView) -> HashMap<Uuid, String> {
+    let mut used = HashSet::new();
+
+    view.objects
+        .iter()
+        .map(|object| {
+            let base = object.name.to_upper_camel_case();
+            let mut name = base.clone();
+            let mut n = 2;
+            while !used.insert(name.clone()) {
+                name = format!("{}{}", base, n);
+                n += 1;
+            }
+            (object.id, name)
+        })
+        .collect()
+}
+
+/// A relationship label, with the phrases from both ends
+///
+/// Something like `R1: owns / is owned by`. Phrases are squashed onto one line,
+/// and missing phrases are left out.
+pub fn relationship_label(rel: &RelationshipView) -> String {
+    let phrases: Vec<String> = match rel {
+        RelationshipView::Binary(binary) => [&binary.from, &binary.to]
+            .into_iter()
+            .map(|side| one_line(&side.description))
+            .filter(|phrase| !phrase.is_empty())
+            .collect(),
+        RelationshipView::Associative(assoc) => [&assoc.one, &assoc.other]
+            .into_iter()
+            .map(|side| one_line(&side.description))
+            .filter(|phrase| !phrase.is_empty())
+            .collect(),
+        RelationshipView::Isa(_) => Vec::new(),
+    };
+
+    if phrases.is_empty() {
+        rel.name()
+    } else {
+        format!("{}: {}", rel.name(), phrases.join(" / "))
+    }
+}
+
+/// Squash whitespace, including newlines, into single spaces
+///
+pub fn one_line(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Escape a string for use in XML, HTML, or GraphViz's HTML-like labels
+///
+pub fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::view::ObjectView;
+
+    fn object(id: u128, name: &str) -> ObjectView {
+        ObjectView {
+            id: Uuid::from_u128(id),
+            name: name.to_owned(),
+            key_letters: String::new(),
+            description: String::new(),
+            attributes: Vec::new(),
+            layout: None,
+        }
+    }
+
+    /// Three objects that all want to be called `FooBar`
+    ///
+    fn model() -> ModelView {
+        ModelView {
+            name: "foo".to_owned(),
+            description: String::new(),
+            domain_ns: Uuid::nil(),
+            extents: (0, 0),
+            offset: (0, 0),
+            objects: vec![
+                object(1, "FooBar"),
+                object(2, "Foo Bar"),
+                object(3, "foo_bar"),
+            ],
+            relationships: Vec::new(),
+            anchors: Default::default(),
+        }
+    }
+
+    #[test]
+    fn colliding_class_names_are_numbered() {
+        let names = class_names(&model());
+
+        assert_eq!(names[&Uuid::from_u128(1)], "FooBar");
+        assert_eq!(names[&Uuid::from_u128(2)], "FooBar2");
+        assert_eq!(names[&Uuid::from_u128(3)], "FooBar3");
+    }
+
+    #[test]
+    fn colliding_classes_are_drawn_separately() {
+        let view = model();
+
+        let mermaid = mermaid::to_mermaid(&view);
+        assert!(mermaid.contains("class FooBar {"));
+        assert!(mermaid.contains("class FooBar2[\"Foo Bar\"] {"));
+        assert!(mermaid.contains("class FooBar3[\"foo_bar\"] {"));
+
+        let uml = plantuml::to_plantuml(&view);
+        assert!(uml.contains("class \"FooBar\" as FooBar {"));
+        assert!(uml.contains("class \"Foo Bar\" as FooBar2 {"));
+        assert!(uml.contains("class \"foo_bar\" as FooBar3 {"));
+    }
+}
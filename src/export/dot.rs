@@ -0,0 +1,114 @@
+//! GraphViz DOT export
+//!
+//! Objects are nodes, drawn as a table with the object's name on top, and its
+//! attributes underneath. Relationships are edges labelled with their number,
+//! and the multiplicity at each end.
+use std::fmt::Write;
+
+use crate::{
+    export::{escape_xml, multiplicity},
+    view::{ModelView, ObjectView, RelationshipView},
+};
+
+/// Render a model as a DOT graph
+///
+pub fn to_dot(view: &ModelView) -> String {
+    let mut dot = String::new();
+
+    writeln!(dot, "digraph \"{}\" {{", escape_string(&view.name)).unwrap();
+    writeln!(dot, "    graph [fontname=\"Helvetica\", overlap=false];").unwrap();
+    writeln!(
+        dot,
+        "    node [shape=plaintext, fontname=\"Helvetica\", fontsize=10];"
+    )
+    .unwrap();
+    writeln!(dot, "    edge [fontname=\"Helvetica\", fontsize=9];").unwrap();
+
+    for object in &view.objects {
+        writeln!(dot).unwrap();
+        write_node(&mut dot, view, object);
+    }
+
+    for rel in &view.relationships {
+        writeln!(dot).unwrap();
+        write_edges(&mut dot, rel);
+    }
+
+    writeln!(dot, "}}").unwrap();
+
+    dot
+}
+
+fn write_node(dot: &mut String, view: &ModelView, object: &ObjectView) {
+    writeln!(dot, "    \"{}\" [label=<", object.id).unwrap();
+    writeln!(
+        dot,
+        "        <table border=\"1\" cellborder=\"0\" cellspacing=\"0\" cellpadding=\"4\">"
+    )
+    .unwrap();
+    writeln!(
+        dot,
+        "            <tr><td bgcolor=\"lightgrey\"><b>{}</b></td></tr>",
+        escape_xml(&object.name)
+    )
+    .unwrap();
+    for attr in &object.attributes {
+        writeln!(
+            dot,
+            "            <tr><td align=\"left\">{}: {}</td></tr>",
+            escape_xml(&attr.name),
+            escape_xml(&attr.ty.name(view))
+        )
+        .unwrap();
+    }
+    writeln!(dot, "        </table>").unwrap();
+    writeln!(dot, "    >];").unwrap();
+}
+
+fn write_edges(dot: &mut String, rel: &RelationshipView) {
+    match rel {
+        RelationshipView::Binary(binary) => {
+            writeln!(
+                dot,
+                "    \"{}\" -> \"{}\" [label=\"{}\", taillabel=\"{}\", headlabel=\"{}\", arrowhead=none];",
+                binary.from.obj_id,
+                binary.to.obj_id,
+                rel.name(),
+                multiplicity(&binary.from),
+                multiplicity(&binary.to)
+            )
+            .unwrap();
+        }
+        RelationshipView::Isa(isa) => {
+            for subtype in &isa.subtypes {
+                writeln!(
+                    dot,
+                    "    \"{}\" -> \"{}\" [label=\"{}\", arrowhead=empty];",
+                    subtype,
+                    isa.supertype,
+                    rel.name()
+                )
+                .unwrap();
+            }
+        }
+        RelationshipView::Associative(assoc) => {
+            for side in [&assoc.one, &assoc.other] {
+                writeln!(
+                    dot,
+                    "    \"{}\" -> \"{}\" [label=\"{}\", headlabel=\"{}\", style=dashed, arrowhead=none];",
+                    assoc.from,
+                    side.obj_id,
+                    rel.name(),
+                    multiplicity(side)
+                )
+                .unwrap();
+            }
+        }
+    }
+}
+
+/// Escape a string for use inside double quotes
+///
+fn escape_string(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,275 @@
+//! JSON Schema export
+//!
+//! This describes what the serde code generated by grace writes, so that people
+//! who aren't using Rust can validate persisted data, or generate types from it.
+//!
+//! Each object is a JSON object, with a property for each attribute, and one for
+//! each referential attribute. Properties are named as the struct fields are:
+//! snake case. Ids are UUID strings, and a reference that may be missing is
+//! nullable. A supertype is an enum, serialized with the name of the subtype as
+//! the key, and the subtype's id as the value. If the supertype has attributes
+//! of its own, or refers to anything, then it's a struct, and the enum is its
+//! `subtype` field.
+//!
+//! The store is a JSON object with a map from id to instance for each object.
+//!
+//! We use the 2020-12 draft. Objects whose names are the same once snake cased
+//! get a number on the end of their file name.
+use std::collections::HashSet;
+
+use anyhow::{Context, Result};
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
+
+use crate::{
+    export::one_line,
+    view::{AttributeType, Conditionality, ModelView, ObjectView, RelationshipView},
+};
+
+const DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+const SCHEMA_EXT: &str = "schema.json";
+
+/// A schema document, and the file it belongs in
+///
+#[derive(Clone, Debug)]
+pub struct Schema {
+    pub file_name: String,
+    pub schema: Value,
+}
+
+impl Schema {
+    /// The schema, pretty printed
+    ///
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(&self.schema).context("😱 unable to serialize schema")
+    }
+}
+
+/// Schemas for every object, and then the store
+///
+pub fn to_json_schemas(view: &ModelView) -> Vec<Schema> {
+    let mut used = HashSet::new();
+    let mut schemas: Vec<Schema> = view
+        .objects
+        .iter()
+        .map(|object| {
+            let file_name = object_file_name(object, &mut used);
+            let mut schema = object_schema(view, object);
+            document(&mut schema, &file_name);
+            Schema { file_name, schema }
+        })
+        .collect();
+
+    schemas.push(store_schema(view));
+
+    schemas
+}
+
+/// The schema for the whole store
+///
+/// It's self contained: every object's schema is included under `$defs`.
+pub fn store_schema(view: &ModelView) -> Schema {
+    let mut defs = Map::new();
+    let mut properties = Map::new();
+    for object in &view.objects {
+        // `FooBar` and `Foo_Bar` are different objects, with the same names
+        // once they've been cased.
+        let base = object.name.to_upper_camel_case();
+        let mut name = base.clone();
+        let mut n = 2;
+        while defs.contains_key(&name) {
+            name = format!("{}{}", base, n);
+            n += 1;
+        }
+        let base = object.name.to_snake_case();
+        let mut property = base.clone();
+        let mut n = 2;
+        while properties.contains_key(&property) {
+            property = format!("{}_{}", base, n);
+            n += 1;
+        }
+        properties.insert(
+            property,
+            json!({
+                "type": "object",
+                "propertyNames": { "format": "uuid" },
+                "additionalProperties": { "$ref": format!("#/$defs/{}", name) },
+            }),
+        );
+        defs.insert(name, object_schema(view, object));
+    }
+
+    let required: Vec<&String> = properties.keys().collect();
+    let mut schema = json!({
+        "title": format!("{} store", view.name),
+        "description": one_line(&view.description),
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "$defs": defs,
+    });
+
+    let file_name = format!("{}.store.{}", view.name.to_snake_case(), SCHEMA_EXT);
+    document(&mut schema, &file_name);
+
+    Schema { file_name, schema }
+}
+
+/// The schema for a single object
+///
+pub fn object_schema(view: &ModelView, object: &ObjectView) -> Value {
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+
+    for attr in &object.attributes {
+        let name = attr.name.to_snake_case();
+        properties.insert(name.clone(), attribute_schema(view, &attr.ty));
+        required.push(name);
+    }
+
+    for rel in view.relationships_of(&object.id) {
+        match rel {
+            RelationshipView::Binary(binary) if binary.from.obj_id == object.id => {
+                let name = binary.referential_attribute.to_snake_case();
+                let ty = if binary.to.conditionality == Conditionality::Conditional {
+                    json!(["string", "null"])
+                } else {
+                    json!("string")
+                };
+                properties.insert(
+                    name.clone(),
+                    json!({
+                        "type": ty,
+                        "format": "uuid",
+                        "description": format!("{}, via {}", view.object_name(&binary.to.obj_id), rel.name()),
+                    }),
+                );
+                required.push(name);
+            }
+            RelationshipView::Associative(assoc) if assoc.from == object.id => {
+                for (attribute, side) in [
+                    (&assoc.one_referential_attribute, &assoc.one),
+                    (&assoc.other_referential_attribute, &assoc.other),
+                ] {
+                    let name = attribute.to_snake_case();
+                    properties.insert(
+                        name.clone(),
+                        json!({
+                            "type": "string",
+                            "format": "uuid",
+                            "description": format!("{}, via {}", view.object_name(&side.obj_id), rel.name()),
+                        }),
+                    );
+                    required.push(name);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Referential attributes may also be modeled attributes.
+    required.sort();
+    required.dedup();
+
+    let mut schema = json!({
+        "title": object.name,
+        "description": one_line(&object.description),
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    });
+
+    // A supertype is an enum of its subtypes.
+    let subtypes: Vec<Uuid> = view
+        .relationships
+        .iter()
+        .filter_map(|r| match r {
+            RelationshipView::Isa(isa) if isa.supertype == object.id => Some(isa.subtypes.clone()),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+    if !subtypes.is_empty() {
+        let variants: Vec<Value> = subtypes
+            .iter()
+            .map(|s| {
+                let name = view.object_name(s).to_upper_camel_case();
+                json!({
+                    "type": "object",
+                    "properties": { name.clone(): { "type": "string", "format": "uuid" } },
+                    "required": [name],
+                    "additionalProperties": false,
+                })
+            })
+            .collect();
+
+        // With nothing but an id, it's just the enum. Otherwise the enum is a
+        // field on the struct. Referential attributes count, since they're
+        // fields too.
+        let referrer = view.relationships_of(&object.id).iter().any(|r| match r {
+            RelationshipView::Binary(binary) => binary.from.obj_id == object.id,
+            RelationshipView::Associative(assoc) => assoc.from == object.id,
+            RelationshipView::Isa(_) => false,
+        });
+        if object.attributes.iter().all(|a| a.name == "id") && !referrer {
+            schema = json!({
+                "title": object.name,
+                "description": one_line(&object.description),
+                "oneOf": variants,
+            });
+        } else {
+            schema["properties"]["subtype"] = json!({ "oneOf": variants });
+            schema["required"]
+                .as_array_mut()
+                .unwrap()
+                .push(json!("subtype"));
+        }
+    }
+
+    schema
+}
+
+fn attribute_schema(view: &ModelView, ty: &AttributeType) -> Value {
+    match ty {
+        AttributeType::Boolean => json!({ "type": "boolean" }),
+        AttributeType::Float => json!({ "type": "number" }),
+        AttributeType::Integer => json!({ "type": "integer" }),
+        AttributeType::String => json!({ "type": "string" }),
+        AttributeType::Uuid => json!({ "type": "string", "format": "uuid" }),
+        AttributeType::Object(id) => json!({
+            "type": "string",
+            "format": "uuid",
+            "description": view.object_name(id),
+        }),
+        // We have no idea what these look like.
+        AttributeType::External(name) => {
+            json!({ "description": format!("External type {}", name) })
+        }
+    }
+}
+
+/// Make a schema a top level document
+///
+fn document(schema: &mut Value, file_name: &str) {
+    if let Some(schema) = schema.as_object_mut() {
+        schema.insert("$schema".to_owned(), json!(DRAFT));
+        schema.insert("$id".to_owned(), json!(file_name));
+    }
+}
+
+/// An object's file name, numbered if it's been used already
+///
+fn object_file_name(object: &ObjectView, used: &mut HashSet<String>) -> String {
+    let base = object.name.to_snake_case();
+
+    let mut stem = base.clone();
+    let mut n = 2;
+    while !used.insert(stem.clone()) {
+        stem = format!("{}_{}", base, n);
+        n += 1;
+    }
+
+    format!("{}.{}", stem, SCHEMA_EXT)
+}
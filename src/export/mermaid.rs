@@ -0,0 +1,126 @@
+//! Mermaid class diagram export
+//!
+//! This is meant to be pasted into Markdown, where GitLab, GitHub, and friends
+//! will render it. Relationships carry UML multiplicities, so conditionality
+//! shows up as a lower bound of zero. Mermaid has no association classes, so an
+//! associative object is drawn with a dotted line to each end.
+use std::fmt::Write;
+
+use uuid::Uuid;
+
+use crate::{
+    export::{class_names, one_line, relationship_label, uml_multiplicity},
+    view::{ModelView, RelationshipView},
+};
+
+/// Render a model as a Mermaid `classDiagram`
+///
+pub fn to_mermaid(view: &ModelView) -> String {
+    let mut mermaid = String::new();
+    let classes = class_names(view);
+    let name = |id: &Uuid| {
+        classes
+            .get(id)
+            .cloned()
+            .unwrap_or_else(|| format!("`{}`", id))
+    };
+
+    writeln!(mermaid, "---").unwrap();
+    writeln!(mermaid, "title: {}", one_line(&view.name)).unwrap();
+    writeln!(mermaid, "---").unwrap();
+    writeln!(mermaid, "classDiagram").unwrap();
+
+    for object in &view.objects {
+        let class = name(&object.id);
+        if class == object.name {
+            writeln!(mermaid, "    class {} {{", class).unwrap();
+        } else {
+            writeln!(
+                mermaid,
+                "    class {}[\"{}\"] {{",
+                class,
+                object.name.replace('"', "'")
+            )
+            .unwrap();
+        }
+        for attr in &object.attributes {
+            writeln!(
+                mermaid,
+                "        +{} {}",
+                member_type(&attr.ty.name(view)),
+                attr.name
+            )
+            .unwrap();
+        }
+        writeln!(mermaid, "    }}").unwrap();
+    }
+
+    for rel in &view.relationships {
+        match rel {
+            RelationshipView::Binary(binary) => {
+                writeln!(
+                    mermaid,
+                    "    {} \"{}\" --> \"{}\" {} : {}",
+                    name(&binary.from.obj_id),
+                    uml_multiplicity(&binary.from),
+                    uml_multiplicity(&binary.to),
+                    name(&binary.to.obj_id),
+                    relationship_label(rel)
+                )
+                .unwrap();
+            }
+            RelationshipView::Isa(isa) => {
+                for subtype in &isa.subtypes {
+                    writeln!(
+                        mermaid,
+                        "    {} <|-- {} : {}",
+                        name(&isa.supertype),
+                        name(subtype),
+                        rel.name()
+                    )
+                    .unwrap();
+                }
+            }
+            RelationshipView::Associative(assoc) => {
+                writeln!(
+                    mermaid,
+                    "    {} \"{}\" -- \"{}\" {} : {}",
+                    name(&assoc.one.obj_id),
+                    uml_multiplicity(&assoc.one),
+                    uml_multiplicity(&assoc.other),
+                    name(&assoc.other.obj_id),
+                    relationship_label(rel)
+                )
+                .unwrap();
+                for side in [&assoc.one, &assoc.other] {
+                    writeln!(
+                        mermaid,
+                        "    {} .. {} : {}",
+                        name(&assoc.from),
+                        name(&side.obj_id),
+                        rel.name()
+                    )
+                    .unwrap();
+                }
+            }
+        }
+    }
+
+    mermaid
+}
+
+/// Spell a type the way Mermaid wants it
+///
+/// External types can be anything at all, e.g., `Vec<Option<String>>` or
+/// `&'a str`. Mermaid writes generics with tildes, and a space would end the
+/// type, so anything else that isn't part of a path becomes an underscore.
+fn member_type(ty: &str) -> String {
+    ty.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c {
+            '<' | '>' => '~',
+            c if c.is_alphanumeric() || c == '_' || c == ':' || c == ',' => c,
+            _ => '_',
+        })
+        .collect()
+}
@@ -0,0 +1,101 @@
+//! PlantUML class diagram export
+//!
+//! Much like the Mermaid export, but PlantUML knows about association classes,
+//! so associative objects are drawn properly.
+use std::fmt::Write;
+
+use uuid::Uuid;
+
+use crate::{
+    export::{class_names, one_line, relationship_label, uml_multiplicity},
+    view::{ModelView, RelationshipView},
+};
+
+/// Render a model as PlantUML source
+///
+pub fn to_plantuml(view: &ModelView) -> String {
+    let mut uml = String::new();
+    let classes = class_names(view);
+    let name = |id: &Uuid| {
+        classes
+            .get(id)
+            .cloned()
+            .unwrap_or_else(|| format!("\"{}\"", id))
+    };
+
+    writeln!(uml, "@startuml").unwrap();
+    writeln!(uml, "title {}", one_line(&view.name)).unwrap();
+    writeln!(uml, "hide empty methods").unwrap();
+    writeln!(uml).unwrap();
+
+    for object in &view.objects {
+        writeln!(
+            uml,
+            "class \"{}\" as {} {{",
+            object.name.replace('"', "'"),
+            name(&object.id)
+        )
+        .unwrap();
+        for attr in &object.attributes {
+            writeln!(uml, "    {} : {}", attr.name, attr.ty.name(view)).unwrap();
+        }
+        writeln!(uml, "}}").unwrap();
+    }
+
+    if !view.relationships.is_empty() {
+        writeln!(uml).unwrap();
+    }
+
+    for rel in &view.relationships {
+        match rel {
+            RelationshipView::Binary(binary) => {
+                writeln!(
+                    uml,
+                    "{} \"{}\" --> \"{}\" {} : {}",
+                    name(&binary.from.obj_id),
+                    uml_multiplicity(&binary.from),
+                    uml_multiplicity(&binary.to),
+                    name(&binary.to.obj_id),
+                    relationship_label(rel)
+                )
+                .unwrap();
+            }
+            RelationshipView::Isa(isa) => {
+                for subtype in &isa.subtypes {
+                    writeln!(
+                        uml,
+                        "{} <|-- {} : {}",
+                        name(&isa.supertype),
+                        name(subtype),
+                        rel.name()
+                    )
+                    .unwrap();
+                }
+            }
+            RelationshipView::Associative(assoc) => {
+                writeln!(
+                    uml,
+                    "{} \"{}\" -- \"{}\" {} : {}",
+                    name(&assoc.one.obj_id),
+                    uml_multiplicity(&assoc.one),
+                    uml_multiplicity(&assoc.other),
+                    name(&assoc.other.obj_id),
+                    relationship_label(rel)
+                )
+                .unwrap();
+                writeln!(
+                    uml,
+                    "({}, {}) .. {}",
+                    name(&assoc.one.obj_id),
+                    name(&assoc.other.obj_id),
+                    name(&assoc.from)
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    writeln!(uml, "@enduml").unwrap();
+
+    uml
+}
@@ -0,0 +1,296 @@
+//! SQL DDL export
+//!
+//! Each object becomes a table, and each attribute a column. Binary
+//! relationships are formalized with a foreign key on the referrer, using the
+//! referential attribute. Associative objects become join tables, with a foreign
+//! key to each of the objects that they associate, again using the referential
+//! attributes.
+//!
+//! SQLite is happy with foreign keys to tables that don't exist yet, so they are
+//! declared in the table. Postgres is not, so they are added at the end, once
+//! all the tables exist.
+//!
+//! Every table is keyed on the object's `id` attribute, and that's what foreign
+//! keys refer to. An object without one can't be exported.
+use std::fmt::Write;
+
+use anyhow::Result;
+use heck::ToSnakeCase;
+use uuid::Uuid;
+
+use crate::{
+    export::one_line,
+    view::{AttributeType, Conditionality, ModelView, ObjectView, RelationshipView},
+};
+
+/// The column every table is keyed on
+const ID: &str = "id";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dialect {
+    Sqlite,
+    Postgres,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ColumnType {
+    Boolean,
+    Float,
+    Integer,
+    Text,
+    Uuid,
+}
+
+impl ColumnType {
+    fn sql(&self, dialect: Dialect) -> &'static str {
+        match (self, dialect) {
+            (ColumnType::Boolean, Dialect::Sqlite) => "INTEGER",
+            (ColumnType::Boolean, Dialect::Postgres) => "BOOLEAN",
+            (ColumnType::Float, Dialect::Sqlite) => "REAL",
+            (ColumnType::Float, Dialect::Postgres) => "DOUBLE PRECISION",
+            (ColumnType::Integer, Dialect::Sqlite) => "INTEGER",
+            (ColumnType::Integer, Dialect::Postgres) => "BIGINT",
+            (ColumnType::Text, _) => "TEXT",
+            (ColumnType::Uuid, Dialect::Sqlite) => "TEXT",
+            (ColumnType::Uuid, Dialect::Postgres) => "UUID",
+        }
+    }
+}
+
+struct Column {
+    name: String,
+    ty: ColumnType,
+    nullable: bool,
+    comment: Option<String>,
+}
+
+struct ForeignKey {
+    column: String,
+    table: String,
+    relationship: String,
+}
+
+struct Table {
+    name: String,
+    comment: Vec<String>,
+    columns: Vec<Column>,
+    foreign_keys: Vec<ForeignKey>,
+    unique: Vec<Vec<String>>,
+}
+
+/// Render a model as a SQL schema
+///
+pub fn to_sql(view: &ModelView, dialect: Dialect) -> Result<String> {
+    let unkeyed: Vec<&str> = view
+        .objects
+        .iter()
+        .filter(|o| !o.attributes.iter().any(|a| a.name.to_snake_case() == ID))
+        .map(|o| o.name.as_str())
+        .collect();
+    anyhow::ensure!(
+        unkeyed.is_empty(),
+        "😱 these objects have no `{}` attribute to use as a primary key: {}",
+        ID,
+        unkeyed.join(", ")
+    );
+
+    let tables: Vec<Table> = view.objects.iter().map(|o| table(view, o)).collect();
+
+    let mut sql = String::new();
+    writeln!(sql, "-- Schema for the {} domain", one_line(&view.name)).unwrap();
+    writeln!(sql, "--").unwrap();
+    writeln!(
+        sql,
+        "-- Generated by sarzak. Edit the model, not this file."
+    )
+    .unwrap();
+
+    for table in &tables {
+        writeln!(sql).unwrap();
+        for line in &table.comment {
+            writeln!(sql, "-- {}", line).unwrap();
+        }
+        writeln!(sql, "CREATE TABLE {} (", quote(&table.name)).unwrap();
+
+        let mut lines = Vec::new();
+        for column in &table.columns {
+            let mut line = format!("    {} {}", quote(&column.name), column.ty.sql(dialect));
+            if column.name == ID {
+                line += " PRIMARY KEY";
+            } else if !column.nullable {
+                line += " NOT NULL";
+            }
+            lines.push((line, column.comment.clone()));
+        }
+        if dialect == Dialect::Sqlite {
+            for fk in &table.foreign_keys {
+                lines.push((
+                    format!(
+                        "    FOREIGN KEY ({}) REFERENCES {} ({})",
+                        quote(&fk.column),
+                        quote(&fk.table),
+                        quote(ID)
+                    ),
+                    Some(fk.relationship.clone()),
+                ));
+            }
+        }
+        for columns in &table.unique {
+            let columns: Vec<String> = columns.iter().map(|c| quote(c)).collect();
+            lines.push((format!("    UNIQUE ({})", columns.join(", ")), None));
+        }
+
+        let count = lines.len();
+        for (i, (line, comment)) in lines.into_iter().enumerate() {
+            let comma = if i + 1 < count { "," } else { "" };
+            match comment {
+                Some(comment) => writeln!(sql, "{}{} -- {}", line, comma, comment).unwrap(),
+                None => writeln!(sql, "{}{}", line, comma).unwrap(),
+            }
+        }
+        writeln!(sql, ");").unwrap();
+    }
+
+    if dialect == Dialect::Postgres {
+        for table in &tables {
+            for fk in &table.foreign_keys {
+                writeln!(sql).unwrap();
+                writeln!(sql, "-- {}", fk.relationship).unwrap();
+                writeln!(
+                    sql,
+                    "ALTER TABLE {} ADD CONSTRAINT {} FOREIGN KEY ({}) REFERENCES {} ({});",
+                    quote(&table.name),
+                    quote(&format!("{}_{}_fk", table.name, fk.column)),
+                    quote(&fk.column),
+                    quote(&fk.table),
+                    quote(ID)
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    Ok(sql)
+}
+
+/// Build the table for an object
+///
+fn table(view: &ModelView, object: &ObjectView) -> Table {
+    let mut table = Table {
+        name: table_name(object),
+        comment: Some(one_line(&object.description))
+            .filter(|s| !s.is_empty())
+            .into_iter()
+            .collect(),
+        columns: Vec::new(),
+        foreign_keys: Vec::new(),
+        unique: Vec::new(),
+    };
+
+    for attr in &object.attributes {
+        let (ty, comment) = match &attr.ty {
+            AttributeType::Boolean => (ColumnType::Boolean, None),
+            AttributeType::Float => (ColumnType::Float, None),
+            AttributeType::Integer => (ColumnType::Integer, None),
+            AttributeType::String => (ColumnType::Text, None),
+            AttributeType::Uuid => (ColumnType::Uuid, None),
+            AttributeType::Object(id) => (
+                ColumnType::Uuid,
+                Some(format!("{} id", view.object_name(id))),
+            ),
+            AttributeType::External(name) => (ColumnType::Text, Some(format!("external {}", name))),
+        };
+        table.columns.push(Column {
+            name: attr.name.to_snake_case(),
+            ty,
+            nullable: false,
+            comment,
+        });
+    }
+
+    for rel in view.relationships_of(&object.id) {
+        match rel {
+            RelationshipView::Binary(binary) if binary.from.obj_id == object.id => {
+                // The referrer might not be related to anything, in which case
+                // there's nothing to refer to.
+                let nullable = binary.to.conditionality == Conditionality::Conditional;
+                let column = binary.referential_attribute.to_snake_case();
+                add_reference(
+                    &mut table,
+                    view,
+                    column,
+                    &binary.to.obj_id,
+                    nullable,
+                    rel.name(),
+                );
+            }
+            RelationshipView::Associative(assoc) if assoc.from == object.id => {
+                let mut columns = Vec::new();
+                for (attribute, side) in [
+                    (&assoc.one_referential_attribute, &assoc.one),
+                    (&assoc.other_referential_attribute, &assoc.other),
+                ] {
+                    let column = attribute.to_snake_case();
+                    columns.push(column.clone());
+                    add_reference(&mut table, view, column, &side.obj_id, false, rel.name());
+                }
+                table.unique.push(columns);
+            }
+            RelationshipView::Isa(isa) if isa.supertype == object.id => {
+                let subtypes: Vec<String> =
+                    isa.subtypes.iter().map(|s| view.object_name(s)).collect();
+                table.comment.push(format!(
+                    "{}: supertype of {}",
+                    rel.name(),
+                    subtypes.join(", ")
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    table
+}
+
+/// Add a foreign key, and its column if the object doesn't already have one
+///
+fn add_reference(
+    table: &mut Table,
+    view: &ModelView,
+    column: String,
+    referent: &Uuid,
+    nullable: bool,
+    relationship: String,
+) {
+    let referent = view
+        .object(referent)
+        .map(table_name)
+        .unwrap_or_else(|| referent.to_string());
+
+    match table.columns.iter_mut().find(|c| c.name == column) {
+        Some(existing) => existing.nullable = nullable,
+        None => table.columns.push(Column {
+            name: column.clone(),
+            ty: ColumnType::Uuid,
+            nullable,
+            comment: None,
+        }),
+    }
+
+    table.foreign_keys.push(ForeignKey {
+        column,
+        table: referent,
+        relationship,
+    });
+}
+
+fn table_name(object: &ObjectView) -> String {
+    object.name.to_snake_case()
+}
+
+/// Quote an identifier
+///
+/// Both SQLite and Postgres go along with the standard double quotes.
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
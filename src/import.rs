@@ -0,0 +1,6 @@
+//! Model importers
+//!
+//! The other direction from [`export`](crate::export): each importer reads some
+//! other format and builds a [`ModelView`](crate::view::ModelView), which can
+//! then be written out as a cuckoo model.
+pub mod sql;
@@ -0,0 +1,903 @@
+//! SQL schema import
+//!
+//! This reads `CREATE TABLE` statements, and `ALTER TABLE ... ADD` statements
+//! for columns and constraints, which is enough to make sense of most schemas,
+//! and certainly those written by `export sql`. Everything else is skipped,
+//! with a note to say so.
+//!
+//! Unquoted identifiers are folded to lower case, as Postgres does, and names
+//! are compared without regard to case.
+//!
+//! Tables become objects, and columns become attributes. Single column foreign
+//! keys become binary relationships, with the column as the referential
+//! attribute. Objects are laid out on a grid, in the order that the tables
+//! appear in the schema.
+use std::{
+    collections::{BTreeMap, HashSet},
+    iter::Peekable,
+    str::Chars,
+};
+
+use anyhow::Result;
+use heck::ToTitleCase;
+use uuid::Uuid;
+
+use crate::{
+    cuckoo::domain_namespace,
+    view::{
+        AttributeType, AttributeView, BinaryView, Cardinality, Conditionality, ModelView,
+        ObjectView, Rect, RelationshipView, Side,
+    },
+};
+
+// Grid layout
+const GRID_GAP: i64 = 80;
+const OBJECT_WIDTH: i64 = 250;
+const OBJECT_HEADER: i64 = 40;
+const ATTRIBUTE_HEIGHT: i64 = 20;
+const MIN_PAPER: (i64, i64) = (1600, 1000);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Table {
+    pub name: String,
+    pub columns: Vec<Column>,
+    pub primary_key: Vec<String>,
+    pub unique: Vec<Vec<String>>,
+    pub foreign_keys: Vec<ForeignKey>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Column {
+    pub name: String,
+    /// The type, as written, e.g., `VARCHAR(255)`
+    pub ty: String,
+    pub nullable: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ForeignKey {
+    pub columns: Vec<String>,
+    pub table: String,
+    pub referenced: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    /// A bare word: a keyword, or an unquoted identifier
+    Word(String),
+    /// A quoted identifier
+    Quoted(String),
+    String(String),
+    Number(String),
+    Punct(char),
+}
+
+impl Token {
+    fn is(&self, keyword: &str) -> bool {
+        matches!(self, Token::Word(w) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn is_punct(&self, c: char) -> bool {
+        self == &Token::Punct(c)
+    }
+
+    fn text(&self) -> String {
+        match self {
+            Token::Word(s) | Token::Quoted(s) | Token::Number(s) => s.clone(),
+            Token::String(s) => format!("'{}'", s),
+            Token::Punct(c) => c.to_string(),
+        }
+    }
+}
+
+/// Parse the tables out of a SQL schema
+///
+/// Statements that we can't use are skipped, and described in the notes that
+/// come back with the tables.
+pub fn parse(sql: &str) -> Result<(Vec<Table>, Vec<String>)> {
+    let tokens = tokenize(sql)?;
+    let mut tables: Vec<Table> = Vec::new();
+    let mut notes = Vec::new();
+
+    for statement in tokens.split(|t| t.is_punct(';')) {
+        if statement.is_empty() {
+            continue;
+        }
+
+        if let Err(error) = parse_statement(statement, &mut tables) {
+            let start: Vec<String> = statement.iter().take(3).map(|t| t.text()).collect();
+            notes.push(format!(
+                "skipped `{} ...`: {}",
+                start.join(" "),
+                error.to_string().trim_start_matches("😱 ")
+            ));
+        }
+    }
+
+    Ok((tables, notes))
+}
+
+fn parse_statement(statement: &[Token], tables: &mut Vec<Table>) -> Result<()> {
+    let mut parser = Parser::new(statement);
+
+    if parser.keyword("CREATE") {
+        parser.any_keyword(&["TEMP", "TEMPORARY", "UNLOGGED"]);
+        anyhow::ensure!(parser.keyword("TABLE"), "only tables are imported");
+        parser.keywords(&["IF", "NOT", "EXISTS"]);
+        let name = parser.name()?;
+        anyhow::ensure!(
+            parser.peek().is_some_and(|t| t.is_punct('(')),
+            "tables created from a query can't be imported"
+        );
+        let table = parser.table_body(name)?;
+        anyhow::ensure!(
+            !parser.keyword("AS"),
+            "tables created from a query can't be imported"
+        );
+        tables.push(table);
+    } else if parser.keywords(&["ALTER", "TABLE"]) {
+        parser.keyword("ONLY");
+        parser.keywords(&["IF", "EXISTS"]);
+        let name = parser.name()?;
+        let table = tables
+            .iter_mut()
+            .find(|t| t.name.eq_ignore_ascii_case(&name))
+            .ok_or_else(|| anyhow::anyhow!("there's no table named {}", name))?;
+
+        // There may be more than one change, separated by commas.
+        loop {
+            Parser::new(parser.element()).alteration(table)?;
+            if parser.advance().is_none() {
+                break;
+            }
+        }
+    } else {
+        anyhow::bail!("only CREATE TABLE and ALTER TABLE are imported");
+    }
+
+    Ok(())
+}
+
+/// Build a model from some tables
+///
+/// Anything that doesn't translate is skipped, and described in the notes
+/// that come back with the model.
+pub fn to_view(tables: &[Table], domain: &str) -> (ModelView, Vec<String>) {
+    let ns = domain_namespace(domain);
+    let mut notes = Vec::new();
+
+    let object_id = |table: &str| Uuid::new_v5(&ns, format!("object:{}", table).as_bytes());
+    let find_table = |name: &str| tables.iter().find(|t| t.name.eq_ignore_ascii_case(name));
+
+    let mut key_letters = HashSet::new();
+    let mut objects = Vec::new();
+    let mut relationships = Vec::new();
+
+    for table in tables {
+        let id = object_id(&table.name);
+
+        // Foreign keys that we can make relationships out of
+        let mut references = Vec::new();
+        for fk in &table.foreign_keys {
+            match (fk.columns.as_slice(), find_table(&fk.table)) {
+                ([column], Some(referent)) => references.push((column.clone(), referent)),
+                (_, None) => notes.push(format!(
+                    "skipped foreign key from {} to unknown table {}",
+                    table.name, fk.table
+                )),
+                _ => notes.push(format!(
+                    "skipped foreign key from {} to {}, which has more than one column",
+                    table.name, fk.table
+                )),
+            }
+        }
+
+        let mut attributes = Vec::new();
+        for column in &table.columns {
+            // Referential attributes come from the relationship, unless they
+            // are also the identifier.
+            let referential = references
+                .iter()
+                .any(|(c, _)| c.eq_ignore_ascii_case(&column.name));
+            let identifying = table
+                .primary_key
+                .iter()
+                .any(|k| k.eq_ignore_ascii_case(&column.name));
+            if referential && !identifying {
+                continue;
+            }
+
+            let ty = attribute_type(&column.name, &column.ty).unwrap_or_else(|| {
+                notes.push(format!(
+                    "{}.{} has type `{}`, which is imported as a String",
+                    table.name, column.name, column.ty
+                ));
+                AttributeType::String
+            });
+            attributes.push(AttributeView {
+                id: Uuid::new_v5(
+                    &ns,
+                    format!("attribute:{}.{}", table.name, column.name).as_bytes(),
+                ),
+                name: column.name.clone(),
+                ty,
+            });
+        }
+
+        for (column, referent) in references {
+            let nullable = table
+                .columns
+                .iter()
+                .find(|c| c.name.eq_ignore_ascii_case(&column))
+                .is_some_and(|c| c.nullable);
+            let just_this =
+                |columns: &[String]| columns.len() == 1 && columns[0].eq_ignore_ascii_case(&column);
+            let one_to_one =
+                just_this(&table.primary_key) || table.unique.iter().any(|u| just_this(u));
+
+            let number = relationships.len() as i64 + 1;
+            relationships.push(RelationshipView::Binary(BinaryView {
+                id: Uuid::new_v5(&ns, format!("relationship:{}", number).as_bytes()),
+                number,
+                from: Side {
+                    obj_id: id,
+                    description: String::new(),
+                    cardinality: if one_to_one {
+                        Cardinality::One
+                    } else {
+                        Cardinality::Many
+                    },
+                    conditionality: Conditionality::Conditional,
+                },
+                to: Side {
+                    obj_id: object_id(&referent.name),
+                    description: String::new(),
+                    cardinality: Cardinality::One,
+                    conditionality: if nullable {
+                        Conditionality::Conditional
+                    } else {
+                        Conditionality::Unconditional
+                    },
+                },
+                referential_attribute: column,
+            }));
+        }
+
+        let name = table.name.to_title_case();
+        objects.push(ObjectView {
+            id,
+            key_letters: unique_key_letters(&name, &mut key_letters),
+            name,
+            description: format!("Imported from the `{}` table.", table.name),
+            attributes,
+            layout: None,
+        });
+    }
+
+    let extents = lay_out(&mut objects);
+
+    let view = ModelView {
+        name: domain.to_owned(),
+        description: "Imported from a SQL schema.".to_owned(),
+        domain_ns: ns,
+        extents,
+        offset: (0, 0),
+        objects,
+        relationships,
+        anchors: BTreeMap::new(),
+    };
+
+    (view, notes)
+}
+
+/// Put the objects on a grid, and return the size of the paper
+///
+fn lay_out(objects: &mut [ObjectView]) -> (i64, i64) {
+    let columns = (objects.len() as f64).sqrt().ceil().max(1.0) as usize;
+
+    let (mut width, mut y) = (0, GRID_GAP);
+    for row in objects.chunks_mut(columns) {
+        let mut row_height = 0;
+        for (i, object) in row.iter_mut().enumerate() {
+            let height = OBJECT_HEADER + ATTRIBUTE_HEIGHT * object.attributes.len().max(1) as i64;
+            let x = GRID_GAP + i as i64 * (OBJECT_WIDTH + GRID_GAP);
+            object.layout = Some(Rect {
+                x,
+                y,
+                width: OBJECT_WIDTH,
+                height,
+            });
+            row_height = row_height.max(height);
+            width = width.max(x + OBJECT_WIDTH + GRID_GAP);
+        }
+        y += row_height + GRID_GAP;
+    }
+
+    (width.max(MIN_PAPER.0), y.max(MIN_PAPER.1))
+}
+
+/// Key letters from the initials of the name, made unique with a number
+///
+fn unique_key_letters(name: &str, taken: &mut HashSet<String>) -> String {
+    let initials: String = name
+        .split_whitespace()
+        .filter_map(|w| w.chars().next())
+        .flat_map(|c| c.to_uppercase())
+        .collect();
+
+    let mut key_letters = initials.clone();
+    let mut n = 1;
+    while !taken.insert(key_letters.clone()) {
+        n += 1;
+        key_letters = format!("{}{}", initials, n);
+    }
+
+    key_letters
+}
+
+/// Map a SQL type onto an attribute type
+///
+/// Text identifiers are taken to be UUIDs, since that's how `export sql`
+/// writes them for SQLite.
+fn attribute_type(column: &str, ty: &str) -> Option<AttributeType> {
+    let base = ty
+        .split(|c: char| c == '(' || c.is_whitespace())
+        .next()
+        .unwrap_or_default()
+        .to_uppercase();
+
+    match base.as_str() {
+        "INT" | "INTEGER" | "SMALLINT" | "BIGINT" | "TINYINT" | "MEDIUMINT" | "INT2" | "INT4"
+        | "INT8" | "SERIAL" | "SMALLSERIAL" | "BIGSERIAL" => Some(AttributeType::Integer),
+        "REAL" | "FLOAT" | "FLOAT4" | "FLOAT8" | "DOUBLE" | "NUMERIC" | "DECIMAL" => {
+            Some(AttributeType::Float)
+        }
+        "BOOL" | "BOOLEAN" => Some(AttributeType::Boolean),
+        "UUID" => Some(AttributeType::Uuid),
+        "TEXT" | "VARCHAR" | "CHAR" | "CHARACTER" | "NCHAR" | "NVARCHAR" | "CLOB" | "CITEXT"
+        | "STRING" => {
+            if column.eq_ignore_ascii_case("id") {
+                Some(AttributeType::Uuid)
+            } else {
+                Some(AttributeType::String)
+            }
+        }
+        _ => None,
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(tokens: &'a [Token]) -> Self {
+        Parser {
+            tokens,
+            position: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn advance(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+        token
+    }
+
+    /// Consume a keyword, if it's next
+    ///
+    fn keyword(&mut self, keyword: &str) -> bool {
+        if self.peek().is_some_and(|t| t.is(keyword)) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Consume a sequence of keywords, if they are all next
+    ///
+    fn keywords(&mut self, keywords: &[&str]) -> bool {
+        let matched = keywords
+            .iter()
+            .enumerate()
+            .all(|(i, k)| self.tokens.get(self.position + i).is_some_and(|t| t.is(k)));
+        if matched {
+            self.position += keywords.len();
+        }
+        matched
+    }
+
+    /// Consume any one of some keywords
+    ///
+    fn any_keyword(&mut self, keywords: &[&str]) -> bool {
+        keywords.iter().any(|k| self.keyword(k))
+    }
+
+    /// An identifier, folded to lower case unless it was quoted
+    ///
+    fn identifier(&mut self) -> Result<String> {
+        match self.advance() {
+            Some(Token::Word(s)) => Ok(s.to_lowercase()),
+            Some(Token::Quoted(s)) => Ok(s.clone()),
+            other => Err(anyhow::anyhow!(
+                "😱 expected an identifier, found {}",
+                other
+                    .map(|t| t.text())
+                    .unwrap_or_else(|| "nothing".to_owned())
+            )),
+        }
+    }
+
+    /// A possibly qualified name, e.g., `public.foo`, of which we keep `foo`
+    ///
+    fn name(&mut self) -> Result<String> {
+        let mut name = self.identifier()?;
+        while self.peek().is_some_and(|t| t.is_punct('.')) {
+            self.position += 1;
+            name = self.identifier()?;
+        }
+        Ok(name)
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        match self.advance() {
+            Some(t) if t.is_punct(c) => Ok(()),
+            other => Err(anyhow::anyhow!(
+                "😱 expected `{}`, found {}",
+                c,
+                other
+                    .map(|t| t.text())
+                    .unwrap_or_else(|| "nothing".to_owned())
+            )),
+        }
+    }
+
+    /// A parenthesized list of identifiers
+    ///
+    fn identifier_list(&mut self) -> Result<Vec<String>> {
+        self.expect('(')?;
+        let mut names = vec![self.identifier()?];
+        loop {
+            match self.advance() {
+                Some(t) if t.is_punct(',') => names.push(self.identifier()?),
+                Some(t) if t.is_punct(')') => return Ok(names),
+                other => {
+                    return Err(anyhow::anyhow!(
+                        "😱 expected `,` or `)`, found {}",
+                        other
+                            .map(|t| t.text())
+                            .unwrap_or_else(|| "nothing".to_owned())
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Everything up to a comma, or the closing paren, at this depth
+    ///
+    fn element(&mut self) -> &'a [Token] {
+        let start = self.position;
+        let mut depth = 0;
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Punct('(') => depth += 1,
+                Token::Punct(')') if depth == 0 => break,
+                Token::Punct(')') => depth -= 1,
+                Token::Punct(',') if depth == 0 => break,
+                _ => {}
+            }
+            self.position += 1;
+        }
+        &self.tokens[start..self.position]
+    }
+
+    fn table_body(&mut self, name: String) -> Result<Table> {
+        let mut table = Table {
+            name,
+            columns: Vec::new(),
+            primary_key: Vec::new(),
+            unique: Vec::new(),
+            foreign_keys: Vec::new(),
+        };
+
+        self.expect('(')?;
+        loop {
+            let mut element = Parser::new(self.element());
+            if element.is_table_constraint() {
+                element.table_constraint(&mut table)?;
+            } else if element.peek().is_some() {
+                element.column(&mut table)?;
+            }
+
+            match self.advance() {
+                Some(t) if t.is_punct(',') => continue,
+                Some(t) if t.is_punct(')') => break,
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "😱 unterminated CREATE TABLE {}",
+                        table.name
+                    ))
+                }
+            }
+        }
+
+        Ok(table)
+    }
+
+    fn is_table_constraint(&self) -> bool {
+        self.peek().is_some_and(|t| {
+            [
+                "CONSTRAINT",
+                "PRIMARY",
+                "FOREIGN",
+                "UNIQUE",
+                "CHECK",
+                "EXCLUDE",
+            ]
+            .iter()
+            .any(|k| t.is(k))
+        })
+    }
+
+    fn table_constraint(&mut self, table: &mut Table) -> Result<()> {
+        if self.keyword("CONSTRAINT") {
+            self.identifier()?;
+        }
+
+        if self.keywords(&["PRIMARY", "KEY"]) {
+            table.primary_key = self.identifier_list()?;
+        } else if self.keyword("UNIQUE") {
+            table.unique.push(self.identifier_list()?);
+        } else if self.keywords(&["FOREIGN", "KEY"]) {
+            let columns = self.identifier_list()?;
+            let (referent, referenced) = self.references()?;
+            table.foreign_keys.push(ForeignKey {
+                columns,
+                table: referent,
+                referenced,
+            });
+        }
+        // Anything else, e.g., CHECK, is of no interest.
+
+        Ok(())
+    }
+
+    /// One change in an `ALTER TABLE`
+    ///
+    /// Only additions are of any interest. A column might be `ADD COLUMN`, or
+    /// just `ADD`.
+    fn alteration(&mut self, table: &mut Table) -> Result<()> {
+        anyhow::ensure!(self.keyword("ADD"), "only ALTER TABLE ... ADD is imported");
+
+        if self.is_table_constraint() {
+            self.table_constraint(table)
+        } else {
+            self.keyword("COLUMN");
+            self.keywords(&["IF", "NOT", "EXISTS"]);
+            self.column(table)
+        }
+    }
+
+    /// `REFERENCES table [(columns)]`
+    ///
+    fn references(&mut self) -> Result<(String, Vec<String>)> {
+        anyhow::ensure!(self.keyword("REFERENCES"), "😱 expected REFERENCES");
+        let table = self.name()?;
+        let columns = if self.peek().is_some_and(|t| t.is_punct('(')) {
+            self.identifier_list()?
+        } else {
+            Vec::new()
+        };
+
+        Ok((table, columns))
+    }
+
+    fn column(&mut self, table: &mut Table) -> Result<()> {
+        const CONSTRAINTS: [&str; 10] = [
+            "CONSTRAINT",
+            "NOT",
+            "NULL",
+            "PRIMARY",
+            "REFERENCES",
+            "DEFAULT",
+            "UNIQUE",
+            "CHECK",
+            "COLLATE",
+            "GENERATED",
+        ];
+
+        let name = self.identifier()?;
+
+        let mut ty = String::new();
+        let mut depth = 0;
+        while let Some(token) = self.peek() {
+            if depth == 0 && CONSTRAINTS.iter().any(|k| token.is(k)) {
+                break;
+            }
+            match token {
+                Token::Punct('(') => depth += 1,
+                Token::Punct(')') => depth -= 1,
+                _ => {}
+            }
+            let text = token.text();
+            if !(ty.is_empty() || ty.ends_with('(') || text == ")" || text == "," || text == "(") {
+                ty.push(' ');
+            }
+            ty.push_str(&text);
+            self.position += 1;
+        }
+
+        let mut column = Column {
+            name: name.clone(),
+            ty,
+            nullable: true,
+        };
+
+        while let Some(token) = self.peek() {
+            if self.keywords(&["NOT", "NULL"]) {
+                column.nullable = false;
+            } else if self.keywords(&["PRIMARY", "KEY"]) {
+                column.nullable = false;
+                table.primary_key = vec![name.clone()];
+            } else if self.keyword("UNIQUE") {
+                table.unique.push(vec![name.clone()]);
+            } else if token.is("REFERENCES") {
+                let (referent, referenced) = self.references()?;
+                table.foreign_keys.push(ForeignKey {
+                    columns: vec![name.clone()],
+                    table: referent,
+                    referenced,
+                });
+            } else {
+                // DEFAULT values, CHECK expressions, and so on.
+                self.position += 1;
+                if token.is_punct('(') {
+                    self.element();
+                    self.position += 1;
+                }
+            }
+        }
+
+        table.columns.push(column);
+
+        Ok(())
+    }
+}
+
+fn tokenize(sql: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = sql.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '-' if chars.peek() == Some(&'-') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                for c in chars.by_ref() {
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+            }
+            '"' => tokens.push(Token::Quoted(quoted(&mut chars, '"')?)),
+            '`' => tokens.push(Token::Quoted(quoted(&mut chars, '`')?)),
+            '[' => tokens.push(Token::Quoted(quoted(&mut chars, ']')?)),
+            '\'' => tokens.push(Token::String(quoted(&mut chars, '\'')?)),
+            '$' => match dollar_quoted(&mut chars) {
+                Some(s) => tokens.push(Token::String(s?)),
+                None => tokens.push(Token::Punct('$')),
+            },
+            c if c.is_ascii_digit() => {
+                let mut number = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_ascii_digit() || c == '.') {
+                        break;
+                    }
+                    number.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Number(number));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut word = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_' || c == '$') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+            c => tokens.push(Token::Punct(c)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Read up to the closing quote, which may be escaped by doubling it
+///
+fn quoted(chars: &mut Peekable<Chars>, close: char) -> Result<String> {
+    let mut s = String::new();
+    loop {
+        match chars.next() {
+            Some(c) if c == close => {
+                if chars.peek() == Some(&close) {
+                    s.push(close);
+                    chars.next();
+                } else {
+                    return Ok(s);
+                }
+            }
+            Some(c) => s.push(c),
+            None => return Err(anyhow::anyhow!("😱 unterminated quote: {}{}", close, s)),
+        }
+    }
+}
+
+/// Read a Postgres dollar quoted string, e.g., `$$ ... $$`, or `$body$ ... $body$`
+///
+/// The opening `$` has been read. If it doesn't start a dollar quote, and
+/// it might be a parameter, e.g., `$1`, then nothing more is read, and we
+/// return `None`.
+fn dollar_quoted(chars: &mut Peekable<Chars>) -> Option<Result<String>> {
+    let mut ahead = chars.clone();
+    let mut tag = String::new();
+    loop {
+        match ahead.next() {
+            Some('$') => break,
+            Some(c) if c.is_alphanumeric() || c == '_' => tag.push(c),
+            _ => return None,
+        }
+    }
+    if tag.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    *chars = ahead;
+
+    let delimiter = format!("${}$", tag);
+    let mut s = String::new();
+    for c in chars.by_ref() {
+        s.push(c);
+        if s.ends_with(&delimiter) {
+            s.truncate(s.len() - delimiter.len());
+            return Some(Ok(s));
+        }
+    }
+
+    Some(Err(anyhow::anyhow!(
+        "😱 unterminated quote: {}{}",
+        delimiter,
+        s
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column_names(table: &Table) -> Vec<&str> {
+        table.columns.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    #[test]
+    fn create_table_as_select_is_skipped() {
+        let (tables, notes) = parse(
+            "CREATE TABLE a (id INTEGER PRIMARY KEY);
+             CREATE TABLE b AS SELECT * FROM a;
+             CREATE TABLE c (x, y) AS SELECT id, id FROM a;
+             CREATE TABLE d (id INTEGER PRIMARY KEY);",
+        )
+        .unwrap();
+
+        let names: Vec<&str> = tables.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["a", "d"]);
+        assert_eq!(notes.len(), 2);
+        assert!(notes.iter().all(|n| n.contains("from a query")));
+    }
+
+    #[test]
+    fn dollar_quoted_bodies() {
+        let (tables, notes) = parse(
+            "CREATE FUNCTION f() RETURNS trigger AS $$
+             BEGIN
+                 RAISE NOTICE 'it''s; not over';
+                 RETURN NEW;
+             END;
+             $$ LANGUAGE plpgsql;
+             CREATE FUNCTION g() RETURNS text AS $body$ SELECT 'don't; $$' $body$ LANGUAGE sql;
+             CREATE TABLE t (id UUID PRIMARY KEY, total NUMERIC DEFAULT $1);",
+        )
+        .unwrap();
+
+        assert_eq!(tables.len(), 1);
+        assert_eq!(column_names(&tables[0]), ["id", "total"]);
+        assert_eq!(notes.len(), 2);
+    }
+
+    #[test]
+    fn unterminated_dollar_quote() {
+        assert!(parse("CREATE FUNCTION f() AS $$ BEGIN").is_err());
+    }
+
+    #[test]
+    fn alter_table_add_column() {
+        let (tables, notes) = parse(
+            "CREATE TABLE a (id INTEGER PRIMARY KEY);
+             CREATE TABLE b (id INTEGER PRIMARY KEY);
+             ALTER TABLE b ADD COLUMN name TEXT NOT NULL, ADD a_id INTEGER;
+             ALTER TABLE ONLY b ADD COLUMN IF NOT EXISTS note TEXT;
+             ALTER TABLE b ADD FOREIGN KEY (a_id) REFERENCES a (id);",
+        )
+        .unwrap();
+
+        assert!(notes.is_empty(), "{:?}", notes);
+        let b = &tables[1];
+        assert_eq!(column_names(b), ["id", "name", "a_id", "note"]);
+        assert!(!b.columns[1].nullable);
+        assert!(b.columns[2].nullable);
+        assert_eq!(b.foreign_keys.len(), 1);
+    }
+
+    #[test]
+    fn unsupported_statements_are_skipped() {
+        let (tables, notes) = parse(
+            "CREATE TABLE a (id INTEGER PRIMARY KEY);
+             CREATE INDEX a_id ON a (id);
+             ALTER TABLE a DROP COLUMN id;
+             ALTER TABLE missing ADD COLUMN x INTEGER;
+             INSERT INTO a VALUES (1);",
+        )
+        .unwrap();
+
+        assert_eq!(tables.len(), 1);
+        assert_eq!(column_names(&tables[0]), ["id"]);
+        assert_eq!(notes.len(), 4);
+    }
+
+    #[test]
+    fn identifiers_are_folded() {
+        let (tables, _) = parse(r#"CREATE TABLE Users (ID INTEGER, UserName TEXT, "DisplayName" TEXT, PRIMARY KEY (Id));"#).unwrap();
+
+        assert_eq!(tables[0].name, "users");
+        assert_eq!(column_names(&tables[0]), ["id", "username", "DisplayName"]);
+        assert_eq!(tables[0].primary_key, ["id"]);
+    }
+
+    #[test]
+    fn keys_are_matched_without_case() {
+        let (tables, _) = parse(
+            r#"CREATE TABLE a (id INTEGER PRIMARY KEY);
+               CREATE TABLE b ("ID" INTEGER REFERENCES A (ID), PRIMARY KEY (id));"#,
+        )
+        .unwrap();
+        let (view, _) = to_view(&tables, "test");
+
+        // The referential attribute is also the identifier, so it stays, and
+        // each b is the only one for its a.
+        let b = view.object_by_name("B").unwrap();
+        assert_eq!(b.attributes.len(), 1);
+        match &view.relationships[0] {
+            RelationshipView::Binary(binary) => {
+                assert_eq!(binary.from.cardinality, Cardinality::One)
+            }
+            other => panic!("expected a binary relationship, found {:?}", other),
+        }
+    }
+}
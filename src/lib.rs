@@ -1,3 +1,16 @@
+pub mod atomic;
 #[cfg(feature = "gui")]
 pub mod boink;
 pub mod config;
+pub mod cuckoo;
+pub mod diff;
+pub mod doc;
+pub mod export;
+pub mod import;
+pub mod lint;
+pub mod lockfile;
+pub mod manifest;
+pub mod merge;
+pub mod model;
+pub mod render;
+pub mod view;
@@ -0,0 +1,411 @@
+//! Model lints
+//!
+//! These look for things in a model that are likely mistakes, or that will
+//! make trouble for a model compiler: objects with no identifier, names that
+//! collide once they are snake cased, attributes that would be Rust keywords,
+//! and the like. How much each one matters is up to the `[lint]` table in
+//! sarzak.toml.
+use std::{collections::HashMap, fmt};
+
+use ansi_term::Colour;
+use heck::ToSnakeCase;
+
+use crate::{
+    config::{Level, LintConfig},
+    view::{ModelView, ObjectView, RelationshipView, Side},
+};
+
+/// The identifying attribute that every object should have
+const IDENTIFIER: &str = "id";
+
+/// Strict and reserved keywords, as of the 2021 edition
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rule {
+    NoIdentifier,
+    DuplicateNames,
+    MissingPhrases,
+    OrphanObjects,
+    RustKeywords,
+    EmptyDescriptions,
+}
+
+impl Rule {
+    /// The rule's key in the `[lint]` table
+    ///
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::NoIdentifier => "no_identifier",
+            Rule::DuplicateNames => "duplicate_names",
+            Rule::MissingPhrases => "missing_phrases",
+            Rule::OrphanObjects => "orphan_objects",
+            Rule::RustKeywords => "rust_keywords",
+            Rule::EmptyDescriptions => "empty_descriptions",
+        }
+    }
+
+    fn level(&self, config: &LintConfig) -> Level {
+        match self {
+            Rule::NoIdentifier => config.no_identifier,
+            Rule::DuplicateNames => config.duplicate_names,
+            Rule::MissingPhrases => config.missing_phrases,
+            Rule::OrphanObjects => config.orphan_objects,
+            Rule::RustKeywords => config.rust_keywords,
+            Rule::EmptyDescriptions => config.empty_descriptions,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Finding {
+    pub rule: Rule,
+    pub level: Level,
+    /// What the problem is with, e.g., "object `Foo`"
+    pub subject: String,
+    pub message: String,
+}
+
+impl Finding {
+    pub fn is_error(&self) -> bool {
+        self.level == Level::Error
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.level {
+            Level::Error => Colour::Red.paint("error"),
+            _ => Colour::Yellow.paint("warning"),
+        };
+
+        write!(
+            f,
+            "{} [{}] {}: {}",
+            level,
+            self.rule.name(),
+            self.subject,
+            self.message
+        )
+    }
+}
+
+/// Lint a model
+///
+/// Rules that are set to `allow` aren't reported.
+pub fn lint(view: &ModelView, config: &LintConfig) -> Vec<Finding> {
+    let mut linter = Linter {
+        config,
+        findings: Vec::new(),
+    };
+
+    let mut objects: Vec<&ObjectView> = view.objects.iter().collect();
+    objects.sort_by(|a, b| a.name.cmp(&b.name));
+
+    for object in &objects {
+        linter.object(view, object);
+    }
+    linter.duplicate_objects(&objects);
+
+    for rel in &view.relationships {
+        linter.relationship(view, rel);
+    }
+
+    linter.findings
+}
+
+struct Linter<'a> {
+    config: &'a LintConfig,
+    findings: Vec<Finding>,
+}
+
+impl<'a> Linter<'a> {
+    fn report<S: Into<String>, M: Into<String>>(&mut self, rule: Rule, subject: S, message: M) {
+        let level = rule.level(self.config);
+        if level != Level::Allow {
+            self.findings.push(Finding {
+                rule,
+                level,
+                subject: subject.into(),
+                message: message.into(),
+            });
+        }
+    }
+
+    fn object(&mut self, view: &ModelView, object: &ObjectView) {
+        let subject = format!("object `{}`", object.name);
+
+        if !object.attributes.iter().any(|a| a.name == IDENTIFIER) {
+            self.report(
+                Rule::NoIdentifier,
+                &subject,
+                format!("has no `{}` attribute", IDENTIFIER),
+            );
+        }
+
+        if object.description.trim().is_empty() {
+            self.report(Rule::EmptyDescriptions, &subject, "has no description");
+        }
+
+        if view.relationships_of(&object.id).is_empty() {
+            self.report(Rule::OrphanObjects, &subject, "is not in any relationship");
+        }
+
+        let mut seen: HashMap<String, &str> = HashMap::new();
+        for attr in &object.attributes {
+            let snake = attr.name.to_snake_case();
+
+            if RUST_KEYWORDS.contains(&snake.as_str()) {
+                self.report(
+                    Rule::RustKeywords,
+                    format!("attribute `{}.{}`", object.name, attr.name),
+                    format!("`{}` is a Rust keyword", snake),
+                );
+            }
+
+            if let Some(other) = seen.insert(snake.clone(), &attr.name) {
+                self.report(
+                    Rule::DuplicateNames,
+                    format!("attribute `{}.{}`", object.name, attr.name),
+                    format!("is `{}` once snake cased, as is `{}`", snake, other),
+                );
+            }
+        }
+    }
+
+    fn duplicate_objects(&mut self, objects: &[&ObjectView]) {
+        let mut seen: HashMap<String, &str> = HashMap::new();
+        for object in objects {
+            let snake = object.name.to_snake_case();
+            if let Some(other) = seen.insert(snake.clone(), &object.name) {
+                self.report(
+                    Rule::DuplicateNames,
+                    format!("object `{}`", object.name),
+                    format!("is `{}` once snake cased, as is `{}`", snake, other),
+                );
+            }
+        }
+    }
+
+    fn relationship(&mut self, view: &ModelView, rel: &RelationshipView) {
+        // Supertypes and subtypes don't have phrases.
+        let sides: Vec<&Side> = match rel {
+            RelationshipView::Binary(binary) => vec![&binary.from, &binary.to],
+            RelationshipView::Associative(assoc) => vec![&assoc.one, &assoc.other],
+            RelationshipView::Isa(_) => return,
+        };
+
+        for side in sides {
+            if side.description.trim().is_empty() {
+                self.report(
+                    Rule::MissingPhrases,
+                    format!("relationship {}", rel.name()),
+                    format!(
+                        "has no phrase on the `{}` side",
+                        view.object_name(&side.obj_id)
+                    ),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::view::{
+        AttributeType, AttributeView, BinaryView, Cardinality, Conditionality, IsaView,
+    };
+
+    fn id(n: u128) -> Uuid {
+        Uuid::from_u128(n)
+    }
+
+    fn object(n: u128, name: &str, attributes: &[&str]) -> ObjectView {
+        ObjectView {
+            id: id(n),
+            name: name.to_owned(),
+            key_letters: name.to_owned(),
+            description: format!("A {}.", name),
+            attributes: attributes
+                .iter()
+                .map(|a| AttributeView {
+                    id: Uuid::new_v5(&id(n), a.as_bytes()),
+                    name: a.to_string(),
+                    ty: AttributeType::Uuid,
+                })
+                .collect(),
+            layout: None,
+        }
+    }
+
+    fn side(n: u128, description: &str) -> Side {
+        Side {
+            obj_id: id(n),
+            description: description.to_owned(),
+            cardinality: Cardinality::One,
+            conditionality: Conditionality::Unconditional,
+        }
+    }
+
+    fn binary(number: i64, from: u128, to: u128) -> RelationshipView {
+        RelationshipView::Binary(BinaryView {
+            id: id(100 + number as u128),
+            number,
+            from: side(from, "refers to"),
+            to: side(to, "is referred to by"),
+            referential_attribute: "ref".to_owned(),
+        })
+    }
+
+    fn view(objects: Vec<ObjectView>, relationships: Vec<RelationshipView>) -> ModelView {
+        ModelView {
+            name: "test".to_owned(),
+            description: String::new(),
+            domain_ns: Uuid::nil(),
+            extents: (0, 0),
+            offset: (0, 0),
+            objects,
+            relationships,
+            anchors: BTreeMap::new(),
+        }
+    }
+
+    /// A model with nothing to complain about
+    ///
+    fn clean() -> ModelView {
+        view(
+            vec![object(1, "Foo", &["id"]), object(2, "Bar", &["id"])],
+            vec![binary(1, 1, 2)],
+        )
+    }
+
+    fn rules(view: &ModelView) -> Vec<(Rule, String)> {
+        lint(view, &LintConfig::default())
+            .into_iter()
+            .map(|f| (f.rule, f.subject))
+            .collect()
+    }
+
+    #[test]
+    fn clean_model() {
+        assert!(rules(&clean()).is_empty());
+    }
+
+    #[test]
+    fn everything_is_a_warning_by_default() {
+        let mut view = clean();
+        view.objects[0].attributes.clear();
+        view.objects[1].description.clear();
+
+        let findings = lint(&view, &LintConfig::default());
+        assert_eq!(findings.len(), 2);
+        assert!(findings.iter().all(|f| f.level == Level::Warn));
+    }
+
+    #[test]
+    fn no_identifier() {
+        let mut view = clean();
+        view.objects[0].attributes[0].name = "key".to_owned();
+
+        assert_eq!(
+            rules(&view),
+            [(Rule::NoIdentifier, "object `Foo`".to_owned())]
+        );
+    }
+
+    #[test]
+    fn duplicate_names() {
+        let mut view = clean();
+        view.objects[1].name = "foo".to_owned();
+        view.objects[0].attributes = object(1, "Foo", &["id", "FooBar", "foo_bar"]).attributes;
+
+        assert_eq!(
+            rules(&view),
+            [
+                (Rule::DuplicateNames, "attribute `Foo.foo_bar`".to_owned()),
+                (Rule::DuplicateNames, "object `foo`".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_phrases() {
+        let mut view = clean();
+        if let RelationshipView::Binary(binary) = &mut view.relationships[0] {
+            binary.to.description = " ".to_owned();
+        }
+        // Supertypes don't need phrases.
+        view.objects.push(object(3, "Baz", &["id"]));
+        view.relationships.push(RelationshipView::Isa(IsaView {
+            id: id(200),
+            number: 2,
+            supertype: id(2),
+            subtypes: vec![id(3)],
+        }));
+
+        assert_eq!(
+            rules(&view),
+            [(Rule::MissingPhrases, "relationship R1".to_owned())]
+        );
+    }
+
+    #[test]
+    fn orphan_objects() {
+        let mut view = clean();
+        view.objects.push(object(3, "Baz", &["id"]));
+
+        assert_eq!(
+            rules(&view),
+            [(Rule::OrphanObjects, "object `Baz`".to_owned())]
+        );
+    }
+
+    #[test]
+    fn rust_keywords() {
+        let mut view = clean();
+        view.objects[0].attributes = object(1, "Foo", &["id", "Type", "types"]).attributes;
+
+        assert_eq!(
+            rules(&view),
+            [(Rule::RustKeywords, "attribute `Foo.Type`".to_owned())]
+        );
+    }
+
+    #[test]
+    fn empty_descriptions() {
+        let mut view = clean();
+        view.objects[1].description = "\n".to_owned();
+
+        assert_eq!(
+            rules(&view),
+            [(Rule::EmptyDescriptions, "object `Bar`".to_owned())]
+        );
+    }
+
+    #[test]
+    fn allowed_rules_are_not_reported() {
+        let mut view = clean();
+        view.objects.push(object(3, "Baz", &[]));
+        let config = LintConfig {
+            no_identifier: Level::Error,
+            orphan_objects: Level::Allow,
+            ..LintConfig::default()
+        };
+
+        let findings = lint(&view, &config);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].rule, Rule::NoIdentifier);
+        assert!(findings[0].is_error());
+    }
+}
@@ -0,0 +1,57 @@
+//! Cargo.lock parsing
+//!
+//! We want to know which versions of sarzak and grace a package is built
+//! against, and so does our build script. This is shared by both, so it
+//! only depends on `serde` and `toml`.
+use serde::Deserialize;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Lockfile {
+    #[serde(default)]
+    pub package: Vec<LockedPackage>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: String,
+    pub source: Option<String>,
+}
+
+impl Lockfile {
+    pub fn parse(lock: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(lock)
+    }
+
+    /// Find a package by name
+    ///
+    /// There may be more than one version of a package in the lock file. We
+    /// return the first one, which is usually good enough for our purposes.
+    pub fn find(&self, name: &str) -> Option<&LockedPackage> {
+        self.package.iter().find(|p| p.name == name)
+    }
+}
+
+impl LockedPackage {
+    /// The git revision, if this package comes from git
+    ///
+    pub fn revision(&self) -> Option<&str> {
+        let source = self.source.as_ref()?;
+        if source.starts_with("git+") {
+            source.rsplit_once('#').map(|(_, rev)| rev)
+        } else {
+            None
+        }
+    }
+
+    /// A short, human friendly description
+    ///
+    /// This is the version, followed by the abbreviated git revision, if there
+    /// is one.
+    pub fn describe(&self) -> String {
+        match self.revision() {
+            Some(rev) => format!("{} ({})", self.version, &rev[..rev.len().min(8)]),
+            None => self.version.clone(),
+        }
+    }
+}
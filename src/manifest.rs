@@ -0,0 +1,222 @@
+//! Generated code manifest
+//!
+//! Each generated module gets a manifest, stored alongside the generated code.
+//! It records what generated the code, and from which model, so that we can
+//! tell when a teammate's older `sarzak` is about to quietly change things.
+//!
+//! It also holds a hash of each generated file, so that we notice when one has
+//! been edited by hand before we go and overwrite it.
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::atomic::write_atomic;
+
+/// The name of the manifest file within a module's directory
+pub const MANIFEST_FILE: &str = ".sarzak-manifest.toml";
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Manifest {
+    pub generator: Generator,
+    /// Generated files, relative to the module directory, and their hashes
+    #[serde(default)]
+    pub files: BTreeMap<String, String>,
+}
+
+/// What generated the code
+///
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Generator {
+    /// The version of sarzak-cli
+    pub sarzak_cli: String,
+    /// The version of grace
+    pub grace: String,
+    /// The git revision of grace, if it came from git
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub grace_revision: String,
+    /// A SHA-256 hash of the model file
+    pub model_hash: String,
+}
+
+impl Manifest {
+    /// Load the manifest from a module directory
+    ///
+    /// Returns `None` if there isn't one.
+    pub fn load<P: AsRef<Path>>(module_dir: P) -> Result<Option<Self>> {
+        let path = module_dir.as_ref().join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let toml = fs::read_to_string(&path)
+            .context(format!("😱 unable to read manifest: {}", path.display()))?;
+        let manifest = toml::from_str(&toml)
+            .context(format!("😱 unable to parse manifest: {}", path.display()))?;
+
+        Ok(Some(manifest))
+    }
+
+    /// Write the manifest to a module directory
+    ///
+    pub fn save<P: AsRef<Path>>(&self, module_dir: P) -> Result<()> {
+        let path = module_dir.as_ref().join(MANIFEST_FILE);
+        let toml = toml::to_string(self).context("😱 unable to serialize manifest")?;
+
+        write_atomic(&path, toml)
+    }
+}
+
+impl Manifest {
+    /// Record the hashes of the generated files in a module directory
+    ///
+    /// `before` is what `hash_files` said before the compiler ran. A file that
+    /// the compiler wrote, or that we've not seen before, gets its new hash.
+    /// Anything else keeps the hash that it had, so that with more than one
+    /// compiler, each compiler's files stay recorded, and hand edits to them
+    /// are still noticed. Files that have gone away are forgotten.
+    pub fn record_files<P: AsRef<Path>>(
+        &mut self,
+        module_dir: P,
+        before: &BTreeMap<String, String>,
+    ) -> Result<()> {
+        let after = hash_files(module_dir)?;
+
+        self.files.retain(|name, _| after.contains_key(name));
+        for (name, hash) in after {
+            if before.get(&name) != Some(&hash) || !self.files.contains_key(&name) {
+                self.files.insert(name, hash);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Find the generated files that have changed since they were recorded
+    ///
+    /// Files that have since been deleted don't count. We'd only be putting
+    /// them back.
+    pub fn modified_files<P: AsRef<Path>>(&self, module_dir: P) -> Result<Vec<PathBuf>> {
+        let module_dir = module_dir.as_ref();
+
+        let mut modified = Vec::new();
+        for (name, hash) in &self.files {
+            let path = module_dir.join(name);
+            if path.exists() && &hash_file(&path)? != hash {
+                modified.push(path);
+            }
+        }
+
+        Ok(modified)
+    }
+}
+
+/// Find the generated files in a module directory
+///
+/// That is, all of the Rust source files.
+pub fn generated_files<P: AsRef<Path>>(module_dir: P) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    collect_files(module_dir.as_ref(), &mut files)?;
+    files.sort();
+
+    Ok(files)
+}
+
+/// Hash each of the generated files in a module directory
+///
+pub fn hash_files<P: AsRef<Path>>(module_dir: P) -> Result<BTreeMap<String, String>> {
+    let module_dir = module_dir.as_ref();
+
+    let mut hashes = BTreeMap::new();
+    for path in generated_files(module_dir)? {
+        hashes.insert(relative_name(module_dir, &path), hash_file(&path)?);
+    }
+
+    Ok(hashes)
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(dir).context(format!("😱 unable to read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// The name we use in the manifest
+///
+/// Always with forward slashes, so that the manifest is the same everywhere.
+fn relative_name(module_dir: &Path, path: &Path) -> String {
+    path.strip_prefix(module_dir)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Hash some bytes
+///
+/// The result is a lowercase hex string.
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Hash a file or a directory
+///
+/// A directory's hash covers the names and contents of all of the files in it,
+/// so that a v2 model directory gets a hash just like a model file does.
+pub fn hash_path<P: AsRef<Path>>(path: P) -> Result<String> {
+    let path = path.as_ref();
+    if !path.is_dir() {
+        return hash_file(path);
+    }
+
+    let mut files = Vec::new();
+    collect_all_files(path, &mut files)?;
+    files.sort();
+
+    let mut hasher = Sha256::new();
+    for file in files {
+        hasher.update(relative_name(path, &file).as_bytes());
+        hasher.update(fs::read(&file).context(format!("😱 unable to read {}", file.display()))?);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn collect_all_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir).context(format!("😱 unable to read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_all_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Hash a file's contents
+///
+pub fn hash_file<P: AsRef<Path>>(path: P) -> Result<String> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).context(format!("😱 unable to read {}", path.display()))?;
+
+    Ok(hash_bytes(&bytes))
+}
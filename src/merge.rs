@@ -0,0 +1,504 @@
+//! Three-way model merge
+//!
+//! Cuckoo models are JSON, and every entity in them is keyed by its UUID. That
+//! makes a structural merge straightforward: walk the three trees together, and
+//! take whichever side changed something. When both sides changed the same
+//! thing differently, that's a conflict. Conflicts are resolved in favor of
+//! `ours`, so that the result is always a usable model, and reported using
+//! object names and relationship numbers rather than JSON paths.
+//!
+//! Lists of UUIDs, like `ids`, are merged as sets.
+//!
+//! Once everything is merged, relationships are checked for references to
+//! objects that one side deleted. Both sides may also have added an object with
+//! the same name, or a relationship with the same number, and those are
+//! conflicts too.
+use std::collections::{BTreeMap, HashSet};
+
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conflict {
+    /// Where the conflict is, e.g., "object `Foo` › attribute `bar` › type"
+    pub subject: String,
+    pub detail: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct Merge {
+    pub model: Value,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Merge two sets of changes to a model
+///
+pub fn merge_models(base: &Value, ours: &Value, theirs: &Value) -> Merge {
+    let mut merger = Merger {
+        roots: [base, ours, theirs],
+        conflicts: Vec::new(),
+    };
+
+    let model = merger
+        .merge(&mut Vec::new(), Some(base), Some(ours), Some(theirs))
+        .unwrap_or(Value::Null);
+    merger.check_references(&model);
+    merger.check_duplicates(&model);
+
+    Merge {
+        model,
+        conflicts: merger.conflicts,
+    }
+}
+
+struct Merger<'a> {
+    /// Base, ours, and theirs; for naming things in conflicts
+    roots: [&'a Value; 3],
+    conflicts: Vec<Conflict>,
+}
+
+impl<'a> Merger<'a> {
+    fn merge(
+        &mut self,
+        path: &mut Vec<String>,
+        base: Option<&Value>,
+        ours: Option<&Value>,
+        theirs: Option<&Value>,
+    ) -> Option<Value> {
+        if ours == theirs {
+            return ours.cloned();
+        }
+        if base == ours {
+            return theirs.cloned();
+        }
+        if base == theirs {
+            return ours.cloned();
+        }
+
+        match (base, ours, theirs) {
+            (base, Some(Value::Object(ours)), Some(Value::Object(theirs))) if !matches!(base, Some(b) if !b.is_object()) =>
+            {
+                let empty = Map::new();
+                let base = base.and_then(|b| b.as_object()).unwrap_or(&empty);
+
+                // Keep our order, and then add theirs.
+                let mut keys: Vec<&String> = ours.keys().collect();
+                keys.extend(theirs.keys().filter(|k| !ours.contains_key(*k)));
+                keys.extend(
+                    base.keys()
+                        .filter(|k| !ours.contains_key(*k) && !theirs.contains_key(*k)),
+                );
+
+                let mut merged = Map::new();
+                for key in keys {
+                    path.push(key.clone());
+                    if let Some(value) =
+                        self.merge(path, base.get(key), ours.get(key), theirs.get(key))
+                    {
+                        merged.insert(key.clone(), value);
+                    }
+                    path.pop();
+                }
+
+                Some(Value::Object(merged))
+            }
+            (base, Some(Value::Array(ours)), Some(Value::Array(theirs)))
+                if is_id_list(ours) && is_id_list(theirs) =>
+            {
+                let empty = Vec::new();
+                let base = base.and_then(|b| b.as_array()).unwrap_or(&empty);
+
+                let merged: Vec<Value> = ours
+                    .iter()
+                    .filter(|id| !base.contains(id) || theirs.contains(id))
+                    .chain(
+                        theirs
+                            .iter()
+                            .filter(|id| !ours.contains(id) && !base.contains(id)),
+                    )
+                    .cloned()
+                    .collect();
+
+                Some(Value::Array(merged))
+            }
+            _ => {
+                self.conflict(path, describe_change(base, ours, theirs));
+                ours.cloned()
+            }
+        }
+    }
+
+    /// Look for relationships that refer to objects that aren't there
+    ///
+    fn check_references(&mut self, model: &Value) {
+        let objects: HashSet<&String> = model
+            .pointer("/objects/entities")
+            .and_then(|e| e.as_object())
+            .map(|e| e.keys().collect())
+            .unwrap_or_default();
+
+        let relationships = match model
+            .pointer("/relationships/entities")
+            .and_then(|e| e.as_object())
+        {
+            Some(relationships) => relationships,
+            None => return,
+        };
+
+        for (id, rel) in relationships {
+            let mut missing = Vec::new();
+            object_references(rel, &mut missing);
+            missing.retain(|obj_id| !objects.contains(obj_id));
+            missing.dedup();
+
+            for obj_id in missing {
+                let path = vec![
+                    "relationships".to_owned(),
+                    "entities".to_owned(),
+                    id.clone(),
+                ];
+                self.conflict(
+                    &path,
+                    format!("refers to {}, which was deleted", self.object_name(&obj_id)),
+                );
+            }
+        }
+    }
+
+    /// Look for objects with the same name, and relationships with the same
+    /// number
+    ///
+    fn check_duplicates(&mut self, model: &Value) {
+        for (kind, key) in [("objects", "name"), ("relationships", "number")] {
+            let entities = match model
+                .pointer(&format!("/{}/entities", kind))
+                .and_then(|e| e.as_object())
+            {
+                Some(entities) => entities,
+                None => continue,
+            };
+
+            let mut seen: BTreeMap<String, Vec<&String>> = BTreeMap::new();
+            for (id, entity) in entities {
+                // Relationships are wrapped in their flavor.
+                let value = entity.get(key).or_else(|| {
+                    entity
+                        .as_object()
+                        .and_then(|o| o.values().next())
+                        .and_then(|inner| inner.get(key))
+                });
+                if let Some(value) = value {
+                    seen.entry(value.to_string()).or_default().push(id);
+                }
+            }
+
+            for ids in seen.values().filter(|ids| ids.len() > 1) {
+                let path = vec![kind.to_owned(), "entities".to_owned(), ids[0].clone()];
+                let what = if kind == "objects" {
+                    "objects have this name"
+                } else {
+                    "relationships have this number"
+                };
+                self.conflict(&path, format!("{} {}", ids.len(), what));
+            }
+        }
+    }
+
+    fn conflict(&mut self, path: &[String], detail: String) {
+        let conflict = Conflict {
+            subject: self.describe_path(path),
+            detail,
+        };
+        self.conflicts.push(conflict);
+    }
+
+    /// Turn a JSON path into something a modeler would recognize
+    ///
+    fn describe_path(&self, path: &[String]) -> String {
+        let mut parts = Vec::new();
+        let mut nodes: Vec<Option<&Value>> = self.roots.iter().map(|r| Some(*r)).collect();
+
+        for segment in path {
+            nodes = nodes
+                .into_iter()
+                .map(|n| n.and_then(|n| n.get(segment)))
+                .collect();
+
+            match segment.as_str() {
+                "entities" | "ids" => continue,
+                "objects" if parts.is_empty() => parts.push("object".to_owned()),
+                "relationships" if parts.is_empty() => parts.push("relationship".to_owned()),
+                "attributes" => parts.push("attribute".to_owned()),
+                s if Uuid::parse_str(s).is_ok() => {
+                    let name = nodes.iter().flatten().find_map(|n| entity_name(n));
+                    let name = name.unwrap_or_else(|| self.object_name(s));
+                    match parts.last_mut() {
+                        // "object" and friends are waiting for a name.
+                        Some(last) if !last.contains(' ') && !last.contains('›') => {
+                            *last = format!("{} {}", last, name)
+                        }
+                        _ => parts.push(name),
+                    }
+                }
+                s => parts.push(s.to_owned()),
+            }
+        }
+
+        if parts.is_empty() {
+            "model".to_owned()
+        } else {
+            parts.join(" › ")
+        }
+    }
+
+    /// An object's name, from whichever side still has it
+    ///
+    fn object_name(&self, id: &str) -> String {
+        self.roots
+            .iter()
+            .find_map(|r| {
+                r.pointer(&format!("/objects/entities/{}/name", id))
+                    .and_then(|n| n.as_str())
+            })
+            .map(|n| format!("`{}`", n))
+            .unwrap_or_else(|| id.to_owned())
+    }
+}
+
+/// The name of an entity: `name` for objects and attributes, and the number
+/// for relationships
+///
+fn entity_name(entity: &Value) -> Option<String> {
+    if let Some(name) = entity.get("name").and_then(|n| n.as_str()) {
+        return Some(format!("`{}`", name));
+    }
+
+    // Relationships are wrapped in their flavor, e.g., `{"Binary": {...}}`.
+    entity
+        .as_object()
+        .filter(|o| o.len() == 1)
+        .and_then(|o| o.values().next())
+        .and_then(|inner| inner.get("number"))
+        .and_then(|n| n.as_i64())
+        .map(|n| format!("R{}", n))
+}
+
+/// Every object that a relationship refers to
+///
+fn object_references(value: &Value, found: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                match (key.as_str(), value) {
+                    ("obj_id", Value::String(id)) => found.push(id.clone()),
+                    ("subtypes", Value::Array(ids)) => found.extend(
+                        ids.iter()
+                            .filter_map(|id| id.as_str())
+                            .map(|id| id.to_owned()),
+                    ),
+                    _ => object_references(value, found),
+                }
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                object_references(value, found);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_id_list(values: &[Value]) -> bool {
+    values
+        .iter()
+        .all(|v| v.as_str().is_some_and(|s| Uuid::parse_str(s).is_ok()))
+}
+
+fn describe_change(base: Option<&Value>, ours: Option<&Value>, theirs: Option<&Value>) -> String {
+    let show = |v: Option<&Value>| match v {
+        None => "deleted".to_owned(),
+        Some(Value::Object(_)) | Some(Value::Array(_)) => "changed".to_owned(),
+        Some(v) => v.to_string(),
+    };
+
+    match base {
+        None => format!(
+            "added on both sides: ours {}, theirs {}",
+            show(ours),
+            show(theirs)
+        ),
+        Some(_) => format!("ours {}, theirs {}", show(ours), show(theirs)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const A: &str = "00000000-0000-0000-0000-00000000000a";
+    const B: &str = "00000000-0000-0000-0000-00000000000b";
+    const C: &str = "00000000-0000-0000-0000-00000000000c";
+    const R1: &str = "00000000-0000-0000-0000-000000000001";
+    const R2: &str = "00000000-0000-0000-0000-000000000002";
+
+    fn object(id: &str, name: &str, description: &str) -> Value {
+        json!({ "id": id, "name": name, "description": description })
+    }
+
+    fn binary(id: &str, number: i64, from: &str, to: &str) -> Value {
+        json!({ "Binary": {
+            "id": id,
+            "number": number,
+            "from": { "obj_id": from },
+            "to": { "obj_id": to },
+        }})
+    }
+
+    fn model(objects: &[Value], relationships: &[Value]) -> Value {
+        let ids = |values: &[Value]| -> Vec<Value> {
+            values
+                .iter()
+                .map(|v| {
+                    v.get("id")
+                        .or_else(|| v.as_object()?.values().next()?.get("id"))
+                        .cloned()
+                        .unwrap()
+                })
+                .collect()
+        };
+        let entities = |values: &[Value]| -> Map<String, Value> {
+            ids(values)
+                .into_iter()
+                .zip(values.iter().cloned())
+                .map(|(id, v)| (id.as_str().unwrap().to_owned(), v))
+                .collect()
+        };
+
+        json!({
+            "objects": { "ids": ids(objects), "entities": entities(objects) },
+            "relationships": { "ids": ids(relationships), "entities": entities(relationships) },
+        })
+    }
+
+    #[test]
+    fn clean_merge() {
+        let base = model(&[object(A, "Foo", "")], &[]);
+        let ours = model(&[object(A, "Foo", "A foo.")], &[]);
+        let theirs = model(&[object(A, "Foo", ""), object(B, "Bar", "")], &[]);
+
+        let merge = merge_models(&base, &ours, &theirs);
+
+        assert!(merge.conflicts.is_empty(), "{:?}", merge.conflicts);
+        assert_eq!(
+            merge.model,
+            model(&[object(A, "Foo", "A foo."), object(B, "Bar", "")], &[])
+        );
+    }
+
+    #[test]
+    fn both_sides_edit() {
+        let base = model(&[object(A, "Foo", "")], &[]);
+        let ours = model(&[object(A, "Foo", "Ours.")], &[]);
+        let theirs = model(&[object(A, "Foo", "Theirs.")], &[]);
+
+        let merge = merge_models(&base, &ours, &theirs);
+
+        assert_eq!(
+            merge.conflicts,
+            [Conflict {
+                subject: "object `Foo` › description".to_owned(),
+                detail: r#"ours "Ours.", theirs "Theirs.""#.to_owned(),
+            }]
+        );
+        assert_eq!(merge.model, ours);
+    }
+
+    #[test]
+    fn delete_and_edit() {
+        let base = model(
+            &[object(A, "Foo", ""), object(B, "Bar", "")],
+            &[binary(R1, 1, A, B)],
+        );
+        let ours = model(&[object(B, "Bar", "")], &[]);
+        let theirs = model(
+            &[object(A, "Foo", "Edited."), object(B, "Bar", "")],
+            &[binary(R1, 1, A, B)],
+        );
+
+        let merge = merge_models(&base, &ours, &theirs);
+
+        assert_eq!(merge.conflicts.len(), 1, "{:?}", merge.conflicts);
+        assert_eq!(merge.conflicts[0].subject, "object `Foo`");
+        assert_eq!(merge.conflicts[0].detail, "ours deleted, theirs changed");
+        assert_eq!(merge.model, ours);
+    }
+
+    #[test]
+    fn deleted_referent() {
+        let base = model(&[object(A, "Foo", ""), object(B, "Bar", "")], &[]);
+        let ours = model(&[object(A, "Foo", "")], &[]);
+        let theirs = model(
+            &[object(A, "Foo", ""), object(B, "Bar", "")],
+            &[binary(R1, 1, A, B)],
+        );
+
+        let merge = merge_models(&base, &ours, &theirs);
+
+        assert_eq!(merge.conflicts.len(), 1, "{:?}", merge.conflicts);
+        assert_eq!(merge.conflicts[0].subject, "relationship R1");
+        assert_eq!(
+            merge.conflicts[0].detail,
+            "refers to `Bar`, which was deleted"
+        );
+    }
+
+    #[test]
+    fn ids_merge_as_sets() {
+        let base = json!({ "ids": [A, B] });
+        let ours = json!({ "ids": [A, B, C] });
+        let theirs = json!({ "ids": [B, R1] });
+
+        let merge = merge_models(&base, &ours, &theirs);
+
+        assert!(merge.conflicts.is_empty(), "{:?}", merge.conflicts);
+        assert_eq!(merge.model, json!({ "ids": [B, C, R1] }));
+    }
+
+    #[test]
+    fn duplicate_names() {
+        let base = model(&[object(A, "Foo", "")], &[]);
+        let ours = model(&[object(A, "Foo", ""), object(B, "Bar", "")], &[]);
+        let theirs = model(&[object(A, "Foo", ""), object(C, "Bar", "")], &[]);
+
+        let merge = merge_models(&base, &ours, &theirs);
+
+        assert_eq!(
+            merge.conflicts,
+            [Conflict {
+                subject: "object `Bar`".to_owned(),
+                detail: "2 objects have this name".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn duplicate_numbers() {
+        let objects = [object(A, "Foo", ""), object(B, "Bar", "")];
+        let base = model(&objects, &[]);
+        let ours = model(&objects, &[binary(R1, 1, A, B)]);
+        let theirs = model(&objects, &[binary(R2, 1, B, A)]);
+
+        let merge = merge_models(&base, &ours, &theirs);
+
+        assert_eq!(
+            merge.conflicts,
+            [Conflict {
+                subject: "relationship R1".to_owned(),
+                detail: "2 relationships have this number".to_owned(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,110 @@
+//! Model loading
+//!
+//! Models come in three flavors. There's the JSON written by cuckoo, the
+//! modeling tool. Then there are the two formats that a v2 `Domain` persists
+//! itself as: a directory of JSON files, and a single bincode file. This sorts
+//! out which is which, and loads any of them into a `Domain`.
+use std::{fs::File, io::Read, path::Path};
+
+use anyhow::{Context, Result};
+use sarzak::{domain::DomainBuilder, v2::domain::Domain};
+
+use crate::{cuckoo, view::ModelView};
+
+/// The file that marks a directory as a v2 model
+const METADATA_FILE: &str = "metadata.json";
+
+const JSON_EXT: &str = "json";
+const BINCODE_EXT: &str = "bin";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// A cuckoo JSON file
+    Cuckoo,
+    /// A directory of JSON files
+    Dir,
+    /// A single bincode file
+    Bin,
+}
+
+impl Format {
+    /// Figure out what sort of model is at `path`
+    ///
+    /// Directories are v2 models, so long as they have a metadata file. Files
+    /// are sorted by extension, and failing that, by whether they look like
+    /// JSON.
+    pub fn detect<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        anyhow::ensure!(
+            path.exists(),
+            format!("😱 Model ({}) does not exist!", path.display())
+        );
+
+        if path.is_dir() {
+            anyhow::ensure!(
+                path.join(METADATA_FILE).exists(),
+                format!("😱 {} is not a model directory!", path.display())
+            );
+            return Ok(Format::Dir);
+        }
+
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(JSON_EXT) => Ok(Format::Cuckoo),
+            Some(BINCODE_EXT) => Ok(Format::Bin),
+            _ => {
+                // Take a peek inside.
+                let mut buffer = [0; 64];
+                let count = File::open(path)
+                    .context(format!("😱 unable to open {}", path.display()))?
+                    .read(&mut buffer)?;
+                let json = buffer[..count]
+                    .iter()
+                    .find(|b| !b.is_ascii_whitespace())
+                    .is_some_and(|b| *b == b'{');
+
+                Ok(if json { Format::Cuckoo } else { Format::Bin })
+            }
+        }
+    }
+}
+
+/// Load a model in any format
+///
+pub fn load_model<P: AsRef<Path>>(path: P) -> Result<Domain> {
+    let path = path.as_ref();
+    load_model_as(path, Format::detect(path)?)
+}
+
+/// Load a model that's known to be in a particular format
+///
+pub fn load_model_as<P: AsRef<Path>>(path: P, format: Format) -> Result<Domain> {
+    let path = path.as_ref();
+
+    match format {
+        Format::Cuckoo => DomainBuilder::new()
+            .cuckoo_model(path)
+            .context(format!("😱 reading model file: {}", path.display()))?
+            .build_v2()
+            .context("😱 building domain"),
+        Format::Dir => Domain::load(path).context(format!("😱 loading model: {}", path.display())),
+        Format::Bin => {
+            Domain::load_bincode(path).context(format!("😱 loading model: {}", path.display()))
+        }
+    }
+}
+
+/// Take a look at a model that was loaded from `path`
+///
+/// A cuckoo model keeps some things that a `Domain` doesn't: how far the paper
+/// was scrolled, and where each relationship was drawn. Those come from the
+/// file.
+pub fn view_model<P: AsRef<Path>>(model: &Domain, path: P) -> Result<ModelView> {
+    let path = path.as_ref();
+
+    let mut view = ModelView::new(model);
+    if Format::detect(path)? == Format::Cuckoo {
+        cuckoo::read_paper(&cuckoo::read_model(path)?, &mut view);
+    }
+
+    Ok(view)
+}
@@ -0,0 +1,445 @@
+//! Headless SVG rendering
+//!
+//! This draws the model the way cuckoo does, minus the GPU. Objects are boxes
+//! at their stored coordinates, moved by the paper's offset. Relationships are
+//! orthogonal lines from where cuckoo anchored them, with the relationship
+//! number in the middle of the line, and the multiplicity at each end.
+//!
+//! Objects that have never been drawn are lined up underneath everything else,
+//! and relationships without anchors are routed between the boxes.
+use std::{collections::HashMap, fmt::Write};
+
+use uuid::Uuid;
+
+use crate::{
+    export::{escape_xml, multiplicity},
+    view::{Anchor, Edge, ModelView, RelationshipView, Side},
+};
+
+/// Space between undrawn objects, and around the edge of the drawing
+const MARGIN: f64 = 40.0;
+const UNDRAWN_WIDTH: f64 = 200.0;
+const UNDRAWN_HEIGHT: f64 = 100.0;
+const HEADER_HEIGHT: f64 = 24.0;
+const LINE_HEIGHT: f64 = 16.0;
+/// How far a reflexive relationship sticks out from its object
+const LOOP_SIZE: f64 = 30.0;
+/// How far a line goes straight out from its anchor before turning
+const ANCHOR_STUB: f64 = 20.0;
+/// How far along the line, and off to the side, multiplicities are drawn
+const END_LABEL_OFFSET: (f64, f64) = (16.0, 10.0);
+
+const OBJECT_FILL: &str = "#fffbe6";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Point {
+    x: f64,
+    y: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Bounds {
+    x0: f64,
+    y0: f64,
+    x1: f64,
+    y1: f64,
+}
+
+/// Where a line leaves an object, and in which direction
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct End {
+    at: Point,
+    edge: Edge,
+}
+
+impl End {
+    fn new(anchor: &Anchor, offset: Point) -> Self {
+        End {
+            at: Point {
+                x: anchor.x as f64 + offset.x,
+                y: anchor.y as f64 + offset.y,
+            },
+            edge: anchor.edge,
+        }
+    }
+
+    /// The end of the stub that sticks straight out of the edge
+    ///
+    fn out(&self) -> Point {
+        let (dx, dy) = match self.edge {
+            Edge::Top => (0.0, -1.0),
+            Edge::Bottom => (0.0, 1.0),
+            Edge::Left => (-1.0, 0.0),
+            Edge::Right => (1.0, 0.0),
+        };
+        Point {
+            x: self.at.x + dx * ANCHOR_STUB,
+            y: self.at.y + dy * ANCHOR_STUB,
+        }
+    }
+
+    /// Where to turn on the way to `to`
+    ///
+    /// Lines leave the top and bottom vertically, and the sides horizontally.
+    fn elbow(&self, to: Point) -> Point {
+        let out = self.out();
+        match self.edge {
+            Edge::Top | Edge::Bottom => Point { x: out.x, y: to.y },
+            Edge::Left | Edge::Right => Point { x: to.x, y: out.y },
+        }
+    }
+}
+
+impl Bounds {
+    fn center(&self) -> Point {
+        Point {
+            x: (self.x0 + self.x1) / 2.0,
+            y: (self.y0 + self.y1) / 2.0,
+        }
+    }
+
+    fn at(point: Point) -> Self {
+        Bounds {
+            x0: point.x,
+            y0: point.y,
+            x1: point.x,
+            y1: point.y,
+        }
+    }
+}
+
+/// Render a model as an SVG document
+///
+pub fn to_svg(view: &ModelView) -> String {
+    let offset = Point {
+        x: view.offset.0 as f64,
+        y: view.offset.1 as f64,
+    };
+    let bounds = place_objects(view, offset);
+
+    let (mut width, mut height) = (view.extents.0 as f64, view.extents.1 as f64);
+    for b in bounds.values() {
+        width = width.max(b.x1 + MARGIN);
+        height = height.max(b.y1 + MARGIN);
+    }
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" font-family=\"Helvetica, Arial, sans-serif\" font-size=\"12\">",
+        w = width,
+        h = height
+    )
+    .unwrap();
+    writeln!(svg, "  <title>{}</title>", escape_xml(&view.name)).unwrap();
+    writeln!(svg, "  <defs>").unwrap();
+    writeln!(
+        svg,
+        "    <marker id=\"isa\" viewBox=\"0 0 12 12\" refX=\"12\" refY=\"6\" markerWidth=\"12\" markerHeight=\"12\" orient=\"auto\">"
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        "      <path d=\"M0,0 L12,6 L0,12 z\" fill=\"white\" stroke=\"black\"/>"
+    )
+    .unwrap();
+    writeln!(svg, "    </marker>").unwrap();
+    writeln!(svg, "  </defs>").unwrap();
+    writeln!(
+        svg,
+        "  <rect width=\"100%\" height=\"100%\" fill=\"white\"/>"
+    )
+    .unwrap();
+
+    // Lines go underneath the boxes.
+    for rel in &view.relationships {
+        write_relationship(&mut svg, rel, &bounds, &anchors(view, rel, offset));
+    }
+
+    for object in &view.objects {
+        let b = bounds[&object.id];
+        writeln!(svg, "  <g id=\"{}\">", object.id).unwrap();
+        writeln!(
+            svg,
+            "    <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" stroke=\"black\"/>",
+            b.x0,
+            b.y0,
+            b.x1 - b.x0,
+            b.y1 - b.y0,
+            OBJECT_FILL
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            "    <text x=\"{}\" y=\"{}\" text-anchor=\"middle\" font-weight=\"bold\">{}</text>",
+            b.center().x,
+            b.y0 + HEADER_HEIGHT - 7.0,
+            escape_xml(&object.name)
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            "    <line x1=\"{}\" y1=\"{y}\" x2=\"{}\" y2=\"{y}\" stroke=\"black\"/>",
+            b.x0,
+            b.x1,
+            y = b.y0 + HEADER_HEIGHT
+        )
+        .unwrap();
+        for (i, attr) in object.attributes.iter().enumerate() {
+            writeln!(
+                svg,
+                "    <text x=\"{}\" y=\"{}\">{}: {}</text>",
+                b.x0 + 6.0,
+                b.y0 + HEADER_HEIGHT + LINE_HEIGHT * (i + 1) as f64,
+                escape_xml(&attr.name),
+                escape_xml(&attr.ty.name(view))
+            )
+            .unwrap();
+        }
+        writeln!(svg, "  </g>").unwrap();
+    }
+
+    writeln!(svg, "</svg>").unwrap();
+
+    svg
+}
+
+/// Figure out where every object goes
+///
+/// Drawn objects stay where they are, give or take the paper's offset, and
+/// the rest are lined up in rows below them.
+fn place_objects(view: &ModelView, offset: Point) -> HashMap<Uuid, Bounds> {
+    let mut bounds = HashMap::new();
+
+    let mut bottom: f64 = 0.0;
+    for object in &view.objects {
+        if let Some(rect) = object.layout {
+            let b = Bounds {
+                x0: rect.x as f64 + offset.x,
+                y0: rect.y as f64 + offset.y,
+                x1: (rect.x + rect.width) as f64 + offset.x,
+                y1: (rect.y + rect.height) as f64 + offset.y,
+            };
+            bottom = bottom.max(b.y1);
+            bounds.insert(object.id, b);
+        }
+    }
+
+    let row_width = (view.extents.0 as f64).max(4.0 * (UNDRAWN_WIDTH + MARGIN));
+    let mut next = Point {
+        x: MARGIN,
+        y: bottom + MARGIN,
+    };
+    for object in view.objects.iter().filter(|o| o.layout.is_none()) {
+        if next.x + UNDRAWN_WIDTH > row_width {
+            next = Point {
+                x: MARGIN,
+                y: next.y + UNDRAWN_HEIGHT + MARGIN,
+            };
+        }
+        bounds.insert(
+            object.id,
+            Bounds {
+                x0: next.x,
+                y0: next.y,
+                x1: next.x + UNDRAWN_WIDTH,
+                y1: next.y + UNDRAWN_HEIGHT,
+            },
+        );
+        next.x += UNDRAWN_WIDTH + MARGIN;
+    }
+
+    bounds
+}
+
+/// The ends of a relationship, as cuckoo anchored them
+///
+/// They're in the same order as `RelationshipView::objects`. Anything short of
+/// an anchor for every object, and we'll route the lines ourselves.
+fn anchors(view: &ModelView, rel: &RelationshipView, offset: Point) -> Option<Vec<End>> {
+    view.anchors
+        .get(&rel.id())
+        .filter(|anchors| anchors.len() == rel.objects().len())
+        .map(|anchors| anchors.iter().map(|a| End::new(a, offset)).collect())
+}
+
+fn write_relationship(
+    svg: &mut String,
+    rel: &RelationshipView,
+    bounds: &HashMap<Uuid, Bounds>,
+    anchors: &Option<Vec<End>>,
+) {
+    // A relationship to an object we don't know about is skipped, rather than
+    // drawn off into the weeds.
+    let lookup = |id: &Uuid| bounds.get(id).copied();
+
+    match rel {
+        RelationshipView::Binary(binary) => {
+            if let (Some(from), Some(to)) = (lookup(&binary.from.obj_id), lookup(&binary.to.obj_id))
+            {
+                let path = match anchors {
+                    Some(ends) => anchored_route(&ends[0], &ends[1]),
+                    None => route(&from, &to),
+                };
+                write_path(svg, &path, "");
+                write_label(svg, midpoint(&path), &rel.name());
+                write_end_labels(svg, &path, &binary.from, &binary.to);
+            }
+        }
+        RelationshipView::Isa(isa) => {
+            if let Some(supertype) = lookup(&isa.supertype) {
+                for (i, subtype) in isa.subtypes.iter().enumerate() {
+                    if let Some(subtype) = lookup(subtype) {
+                        let path = match anchors {
+                            Some(ends) => anchored_route(&ends[i + 1], &ends[0]),
+                            None => route(&subtype, &supertype),
+                        };
+                        write_path(svg, &path, " marker-end=\"url(#isa)\"");
+                        write_label(svg, midpoint(&path), &rel.name());
+                    }
+                }
+            }
+        }
+        RelationshipView::Associative(assoc) => {
+            if let (Some(from), Some(one), Some(other)) = (
+                lookup(&assoc.from),
+                lookup(&assoc.one.obj_id),
+                lookup(&assoc.other.obj_id),
+            ) {
+                let path = match anchors {
+                    Some(ends) => anchored_route(&ends[1], &ends[2]),
+                    None => route(&one, &other),
+                };
+                let middle = midpoint(&path);
+                write_path(svg, &path, "");
+                write_end_labels(svg, &path, &assoc.one, &assoc.other);
+
+                let tie = match anchors {
+                    Some(ends) => {
+                        let from = ends[0];
+                        vec![from.at, from.out(), from.elbow(middle), middle]
+                    }
+                    None => route(&from, &Bounds::at(middle)),
+                };
+                write_path(svg, &tie, " stroke-dasharray=\"6,4\"");
+                write_label(svg, midpoint(&tie), &rel.name());
+            }
+        }
+    }
+}
+
+/// Route a line between two anchors
+///
+/// Straight out of each anchor, and one turn in between.
+fn anchored_route(a: &End, b: &End) -> Vec<Point> {
+    let (start, end) = (a.out(), b.out());
+    vec![a.at, start, a.elbow(end), end, b.at]
+}
+
+/// Route a line from one box to another
+///
+/// Boxes that line up vertically or horizontally get a straight line. The
+/// rest get a dog-leg: out the side facing the other box, across halfway, and
+/// over. A box related to itself gets a loop off its right hand side.
+fn route(a: &Bounds, b: &Bounds) -> Vec<Point> {
+    if a == b {
+        let y = a.center().y;
+        let h = (a.y1 - a.y0) / 4.0;
+        return vec![
+            Point { x: a.x1, y: y - h },
+            Point {
+                x: a.x1 + LOOP_SIZE,
+                y: y - h,
+            },
+            Point {
+                x: a.x1 + LOOP_SIZE,
+                y: y + h,
+            },
+            Point { x: a.x1, y: y + h },
+        ];
+    }
+
+    let (ca, cb) = (a.center(), b.center());
+
+    let (left, right) = (a.x0.max(b.x0), a.x1.min(b.x1));
+    if left <= right {
+        let x = (left + right) / 2.0;
+        return if a.y1 <= b.y0 {
+            vec![Point { x, y: a.y1 }, Point { x, y: b.y0 }]
+        } else if b.y1 <= a.y0 {
+            vec![Point { x, y: a.y0 }, Point { x, y: b.y1 }]
+        } else {
+            // Overlapping boxes; there's no good answer.
+            vec![ca, cb]
+        };
+    }
+
+    let (top, bottom) = (a.y0.max(b.y0), a.y1.min(b.y1));
+    if top <= bottom {
+        let y = (top + bottom) / 2.0;
+        return if a.x1 <= b.x0 {
+            vec![Point { x: a.x1, y }, Point { x: b.x0, y }]
+        } else {
+            vec![Point { x: a.x0, y }, Point { x: b.x1, y }]
+        };
+    }
+
+    let (start, end) = if a.x1 <= b.x0 {
+        (Point { x: a.x1, y: ca.y }, Point { x: b.x0, y: cb.y })
+    } else {
+        (Point { x: a.x0, y: ca.y }, Point { x: b.x1, y: cb.y })
+    };
+    let x = (start.x + end.x) / 2.0;
+
+    vec![start, Point { x, y: start.y }, Point { x, y: end.y }, end]
+}
+
+/// The middle of the middle segment of a path
+///
+fn midpoint(path: &[Point]) -> Point {
+    let (a, b) = (path[path.len() / 2 - 1], path[path.len() / 2]);
+    Point {
+        x: (a.x + b.x) / 2.0,
+        y: (a.y + b.y) / 2.0,
+    }
+}
+
+fn write_path(svg: &mut String, path: &[Point], attributes: &str) {
+    let points: Vec<String> = path.iter().map(|p| format!("{},{}", p.x, p.y)).collect();
+    writeln!(
+        svg,
+        "  <polyline points=\"{}\" fill=\"none\" stroke=\"black\"{}/>",
+        points.join(" "),
+        attributes
+    )
+    .unwrap();
+}
+
+fn write_label(svg: &mut String, at: Point, text: &str) {
+    writeln!(
+        svg,
+        "  <text x=\"{}\" y=\"{}\" text-anchor=\"middle\" dominant-baseline=\"middle\" stroke=\"white\" stroke-width=\"3\" paint-order=\"stroke\">{}</text>",
+        at.x,
+        at.y,
+        escape_xml(text)
+    )
+    .unwrap();
+}
+
+/// Label each end of a path with its multiplicity
+///
+fn write_end_labels(svg: &mut String, path: &[Point], start: &Side, end: &Side) {
+    let n = path.len();
+    for (from, toward, side) in [(path[0], path[1], start), (path[n - 1], path[n - 2], end)] {
+        let (dx, dy) = (toward.x - from.x, toward.y - from.y);
+        let length = (dx * dx + dy * dy).sqrt().max(1.0);
+        let (ux, uy) = (dx / length, dy / length);
+        let (along, aside) = END_LABEL_OFFSET;
+        let at = Point {
+            x: from.x + ux * along - uy * aside,
+            y: from.y + uy * along + ux * aside,
+        };
+        write_label(svg, at, multiplicity(side));
+    }
+}
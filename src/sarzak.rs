@@ -1,11 +1,17 @@
 use std::{
+    collections::BTreeMap,
     ffi::OsString,
     fs,
     fs::File,
     io::{Read, Write},
     os::unix::ffi::OsStringExt,
-    path::PathBuf,
-    process, time,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, MutexGuard,
+    },
+    time,
 };
 
 use ansi_term::Colour;
@@ -13,6 +19,7 @@ use anyhow::{anyhow, Context, Result};
 use clap::{ArgAction, Parser, Subcommand, ValueEnum};
 use heck::{ToSnakeCase, ToTitleCase};
 use log::{debug, error, warn};
+use serde::Serialize;
 use toml::{Table, Value};
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 use uuid::Uuid;
@@ -21,22 +28,93 @@ use uuid::Uuid;
 // use chacha::dwarf::{parse_dwarf, populate_lu_dog, DwarfOptions};
 use grace::GraceCompilerOptions;
 use nut::codegen::{emitln, CachingContext};
-use sarzak::{domain::DomainBuilder, mc::SarzakModelCompiler, v2::domain::Domain};
-use sarzak_cli::config::{Compiler as CompilerOptions, Config, ModuleConfig};
+use sarzak::{mc::SarzakModelCompiler, v2::domain::Domain};
+use sarzak_cli::{
+    atomic::{replace_atomic, write_atomic, Lock, Snapshot},
+    config::{Compiler as CompilerOptions, Config, LintConfig, ModuleConfig},
+    cuckoo,
+    diff::{diff_conversion, diff_views},
+    doc, export, import,
+    lint::{lint, Finding},
+    lockfile::Lockfile,
+    manifest::{hash_files, hash_path, Generator, Manifest},
+    merge::merge_models,
+    model::{load_model, load_model_as, view_model, Format},
+    render,
+    view::ModelView,
+};
 
 const SARZAK_CONFIG_TOML: &str = "sarzak.toml";
+const CONFIG_TEMPLATE: &str = r#"# sarzak configuration
+#
+# Each domain in this package gets a table under [modules], keyed by the name of
+# the Rust module that holds its generated code. `sarzak new` adds these for you.
+#
+# [modules.my_domain]
+# model = "models/my_domain.json"
+#
+# [[modules.my_domain.compiler]]
+# compiler = "grace"
+#
+# Models are linted before code is generated, and by `sarzak lint`. Each lint
+# may be set to "allow", "warn", or "error", and errors stop `gen`.
+#
+# [lint]
+# orphan_objects = "allow"
+
+[modules]
+"#;
 
 const BLANK_MODEL: &str = include_str!("../models/blank.json");
 const MODEL_DIR: &str = "models";
 
 const METADATA_FILE: &str = "metadata.json";
+const GIT_ATTRIBUTES: &str = ".gitattributes";
+const MERGE_DRIVER: &str = "sarzak";
+
+// Where we keep our scratch files, relative to the package root.
+const SCRATCH_DIR: &str = "target/sarzak";
+const LOCK_FILE: &str = "gen.lock";
+const SNAPSHOT_DIR: &str = "snapshot";
 
 const JSON_EXT: &str = "json";
+const GIT_PREFIX: &str = "git:";
 const BINCODE_EXT: &str = "bin";
+const V2_EXT: &str = "v2.json";
+
+// Converted model naming
+const NAME_PLACEHOLDER: &str = "{name}";
+const EXT_PLACEHOLDER: &str = "{ext}";
+const DEFAULT_OUTPUT_TEMPLATE: &str = "{name}.{ext}";
+
+// Versions of the things that we were built with. See build.rs.
+const SARZAK_VERSION: (&str, &str) = (
+    env!("SARZAK_CLI_SARZAK_VERSION"),
+    env!("SARZAK_CLI_SARZAK_REVISION"),
+);
+const GRACE_VERSION: (&str, &str) = (
+    env!("SARZAK_CLI_GRACE_VERSION"),
+    env!("SARZAK_CLI_GRACE_REVISION"),
+);
+const NUT_VERSION: (&str, &str) = (
+    env!("SARZAK_CLI_NUT_VERSION"),
+    env!("SARZAK_CLI_NUT_REVISION"),
+);
+
+// Set when we receive a SIGINT during `gen`.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+// The snapshot of the module being generated, so that a second SIGINT can put
+// it back before we go.
+static SNAPSHOT: Mutex<Option<Snapshot>> = Mutex::new(None);
 
 // Exit codes
 const MODULE_EXISTS: i32 = -1;
 const NOTHING_TO_DO: i32 = -3;
+const FILES_MODIFIED: i32 = -4;
+const MERGE_CONFLICTS: i32 = -5;
+const MODELS_UNFORMATTED: i32 = -6;
+const LINT_ERRORS: i32 = -7;
+const ABORTED: i32 = -8;
 
 #[derive(Debug, Parser)]
 #[command(author, version, about, long_about = None)]
@@ -71,6 +149,23 @@ struct Args {
 
 #[derive(Debug, Subcommand)]
 enum Command {
+    /// Prepare a package for sarzak
+    ///
+    /// This creates a sarzak.toml, the `models` directory, and ignores the
+    /// cached `.v2.json` models in `.gitignore`. It doesn't create a domain;
+    /// that's what `new` is for.
+    ///
+    /// Once that's done, the environment is checked for things that will
+    /// trip you up later.
+    Init,
+    /// Check for problems
+    ///
+    /// Report the versions of sarzak, grace, and nut that this binary was
+    /// built with, and compare them with those used by the package. Weird
+    /// compiler errors are the usual symptom of a mismatch.
+    ///
+    /// The config file and the model files are checked as well.
+    Doctor,
     /// Create a new domain
     ///
     /// This involves creating a new module within a Rust package, as well as a
@@ -92,6 +187,73 @@ enum Command {
         /// The name of the Rust module that will contain the generated source
         /// code. If not supplied the module name will match the domain name.
         module: Option<String>,
+        /// Existing model
+        ///
+        /// Start the new domain as a copy of an existing model file. The
+        /// objects and relationships are copied, and every UUID is regenerated
+        /// under the new domain's namespace.
+        #[arg(long)]
+        from: Option<PathBuf>,
+    },
+    /// Remove a domain
+    ///
+    /// This is the opposite of `new`. The module is removed from sarzak.toml,
+    /// and the model file, the cached `.v2.json` model, and the module's
+    /// source code are all deleted. The module is also removed from the crate
+    /// root.
+    Remove {
+        /// Module name
+        ///
+        /// The name of the module, as it appears in sarzak.toml.
+        module: String,
+        /// Keep the model
+        ///
+        /// Don't delete the model file. The cached `.v2.json` model is still
+        /// removed.
+        #[arg(long, action=ArgAction::SetTrue)]
+        keep_model: bool,
+        /// Don't ask
+        ///
+        /// Skip the confirmation prompt.
+        #[arg(long, short, action=ArgAction::SetTrue)]
+        yes: bool,
+    },
+    /// Rename a domain
+    ///
+    /// By default both the domain and the module are renamed. The domain is
+    /// the model file and the name on the model's paper. The module is the
+    /// entry in sarzak.toml, the source code, and any paths that refer to it.
+    ///
+    /// The domain's namespace is left alone, so UUIDs are stable across the
+    /// rename.
+    Rename {
+        /// Module name
+        ///
+        /// The current name of the module, as it appears in sarzak.toml.
+        old: String,
+        /// New name
+        ///
+        /// The new domain name. The module name is derived from it, just as it
+        /// is with `new`.
+        new: String,
+        /// Only rename the module
+        ///
+        #[arg(long, action=ArgAction::SetTrue, conflicts_with = "domain_only")]
+        module_only: bool,
+        /// Only rename the domain
+        ///
+        #[arg(long, action=ArgAction::SetTrue)]
+        domain_only: bool,
+    },
+    /// List modules
+    ///
+    /// Show each module in sarzak.toml, along with the state of its model, its
+    /// cached `.v2.json` model, and its generated code.
+    List {
+        /// Output format
+        ///
+        #[arg(long, short, value_enum, default_value_t = ListFormat::Table)]
+        format: ListFormat,
     },
     /// Generate code
     ///
@@ -106,16 +268,204 @@ enum Command {
         #[arg(long, short, use_value_delimiter = true, value_delimiter = ',')]
         modules: Option<Vec<String>>,
 
+        /// Strict mode
+        ///
+        /// Refuse to generate code for a module that was last generated by a
+        /// newer version of sarzak or grace. Normally this is just a warning.
+        #[arg(long, action=ArgAction::SetTrue)]
+        strict: bool,
+
+        /// Overwrite hand edits
+        ///
+        /// Generate code even if generated files have been edited by hand
+        /// since they were last generated. Normally we refuse.
+        #[arg(long, action=ArgAction::SetTrue, conflicts_with = "backup")]
+        force: bool,
+
+        /// Back up hand edits
+        ///
+        /// Copy generated files that have been edited by hand to
+        /// `<file>.orig` before generating code.
+        #[arg(long, action=ArgAction::SetTrue)]
+        backup: bool,
+
         #[command(subcommand)]
         compiler: Option<Compiler>,
     },
+    /// Verify generated code
+    ///
+    /// List every generated file that has been edited by hand since it was
+    /// generated.
+    Verify {
+        /// Module name(s)
+        ///
+        /// The comma separated list of modules to verify. If this argument is
+        /// not included, then all modules in the sarzak.toml file are verified.
+        #[arg(long, short, use_value_delimiter = true, value_delimiter = ',')]
+        modules: Option<Vec<String>>,
+    },
+    /// Lint models
+    ///
+    /// Look for likely mistakes in each model, such as objects with no
+    /// identifier, or attributes named with Rust keywords. How seriously each
+    /// is taken is set in the `[lint]` table in sarzak.toml. The same lints are
+    /// run by `gen`, and errors stop it.
+    Lint {
+        /// Module name(s)
+        ///
+        /// The comma separated list of modules to lint. If this argument is
+        /// not included, then all modules in the sarzak.toml file are linted.
+        #[arg(long, short, use_value_delimiter = true, value_delimiter = ',')]
+        modules: Option<Vec<String>>,
+    },
     /// Convert model formats
     ///
-    /// Convert a JSON model to either a directory of `.json` files, or a single
-    /// binary encoded `.bin` file.
+    /// Convert a model between cuckoo JSON, a directory of `.json` files, and a
+    /// single binary encoded `.bin` file. The input format is detected.
     Convert {
+        #[arg(required_unless_present = "all")]
+        domain: Option<String>,
+        #[arg(required_unless_present = "all")]
+        format: Option<ModelFormat>,
+        out_dir: Option<PathBuf>,
+        /// Convert every model
+        ///
+        /// Convert the model of every module in sarzak.toml to the given format.
+        /// Converted models are written next to the originals, unless an output
+        /// directory is given with `--out-dir`.
+        #[arg(long, value_name = "FORMAT", conflicts_with_all = ["domain", "format"])]
+        all: Option<ModelFormat>,
+        /// Output directory
+        ///
+        /// The same as the positional `out_dir`, which isn't available when
+        /// converting with `--all`.
+        #[arg(long = "out-dir", value_name = "OUT_DIR", conflicts_with = "out_dir")]
+        out_dir_flag: Option<PathBuf>,
+        /// Output file
+        ///
+        /// Either the full path of the converted model, or a template for it.
+        /// `{name}` is replaced with the name of the model, and `{ext}` with the
+        /// extension for the format, e.g., `dist/{name}-1.0.{ext}`. The default
+        /// is `{name}.{ext}`, in the output directory.
+        #[arg(long, short, conflicts_with_all = ["out_dir", "out_dir_flag"])]
+        output: Option<String>,
+        /// Verify the conversion
+        ///
+        /// Read the converted model back in, and compare it with the original.
+        /// Any differences are reported, and are an error.
+        #[arg(long, action=ArgAction::SetTrue)]
+        verify: bool,
+    },
+    /// Compare two models
+    ///
+    /// Objects, attributes, and relationships are compared by name, and the
+    /// differences are listed. Layout changes are listed separately.
+    Diff {
+        /// The original model
+        ///
+        /// A domain name, a path to a model file, or `git:<rev>:<path>` for a
+        /// model file as it was in some git revision, e.g.,
+        /// `git:HEAD:models/foo.json`. The path is relative to the root of the
+        /// repository, unless it starts with `./`, in which case it's relative
+        /// to the package.
+        a: String,
+        /// The changed model
+        ///
+        /// The same as the original model.
+        b: String,
+        /// Don't list layout changes
+        #[arg(long, action=ArgAction::SetTrue)]
+        ignore_layout: bool,
+    },
+    /// Merge two sets of changes to a model
+    ///
+    /// This is a three-way merge: objects, attributes, and relationships are
+    /// matched by id, and whatever changed on only one side is kept. Where both
+    /// sides changed the same thing, ours wins, the conflict is reported, and
+    /// we exit with an error.
+    ///
+    /// This is what git runs once `install-merge-driver` has been run.
+    Merge {
+        /// The common ancestor
+        base: PathBuf,
+        /// Our version
+        ours: PathBuf,
+        /// Their version
+        theirs: PathBuf,
+        /// Output file
+        ///
+        /// The merged model is written to stdout if this is not given.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Format model files
+    ///
+    /// Rewrite each model in a canonical form, so that the order that some
+    /// editor happened to write things in doesn't show up in diffs. Only cuckoo
+    /// JSON models are formatted.
+    Fmt {
+        /// Module name(s)
+        ///
+        /// The comma separated list of modules to format. If this argument is
+        /// not included, then all modules in the sarzak.toml file are formatted.
+        #[arg(long, short, use_value_delimiter = true, value_delimiter = ',')]
+        modules: Option<Vec<String>>,
+        /// Don't write anything, just list the models that need formatting
+        ///
+        /// Any are an error, which makes this suitable for CI.
+        #[arg(long, action=ArgAction::SetTrue)]
+        check: bool,
+    },
+    /// Have git use `sarzak merge` for models
+    ///
+    /// This registers the merge driver in the repository's `.git/config`, and
+    /// adds the model files to `.gitattributes`.
+    InstallMergeDriver,
+    /// Import a model
+    ///
+    /// Create a new domain from something that isn't a model. The domain is
+    /// added to the package just as it is by `new`.
+    Import {
+        #[command(subcommand)]
+        source: ImportSource,
+    },
+    /// Export a model
+    ///
+    /// Export a domain to another format, for use outside of sarzak. The output
+    /// is written to stdout, unless a file is given with `--output`.
+    Export {
+        #[command(subcommand)]
+        format: ExportFormat,
+    },
+    /// Render a model diagram as SVG
+    ///
+    /// Objects are drawn where they sit on the paper, and relationships are
+    /// drawn from where they're anchored. This doesn't need a GPU, or the `gui`
+    /// feature.
+    Render {
+        /// Domain name, or path to a model file
+        domain: String,
+        /// Output file
+        ///
+        /// The SVG is written to stdout if this is not given.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Document a domain
+    ///
+    /// Write a small documentation site for a domain: an index page with the
+    /// model diagram, and a page for each object with its description,
+    /// attributes, and relationships.
+    Doc {
+        /// Domain name, or path to a model file
         domain: String,
-        format: ModelFormat,
+        /// Documentation format
+        #[arg(long, short, value_enum, default_value_t = DocFormat::Html)]
+        format: DocFormat,
+        /// Output directory
+        ///
+        /// The default is `target/sarzak/doc/<domain>`.
+        #[arg(long, short)]
         out_dir: Option<PathBuf>,
     },
     /// Display a model
@@ -135,14 +485,201 @@ enum Command {
     },
 }
 
+impl Command {
+    /// Make the output paths absolute
+    ///
+    /// They're relative to where we were run, but `find_package_dir` changes to
+    /// the package directory before anything is written.
+    fn resolve_paths(&mut self, cwd: &Path) {
+        let resolve = |path: &mut Option<PathBuf>| {
+            if let Some(path) = path {
+                *path = cwd.join(&path);
+            }
+        };
+
+        match self {
+            Command::Convert {
+                out_dir,
+                out_dir_flag,
+                output,
+                ..
+            } => {
+                resolve(out_dir);
+                resolve(out_dir_flag);
+                if let Some(output) = output {
+                    *output = cwd.join(&output).to_string_lossy().into_owned();
+                }
+            }
+            Command::Merge { output, .. } | Command::Render { output, .. } => resolve(output),
+            Command::Doc { out_dir, .. } => resolve(out_dir),
+            Command::Export { format } => match format {
+                ExportFormat::Dot { output, .. }
+                | ExportFormat::Mermaid { output, .. }
+                | ExportFormat::Plantuml { output, .. }
+                | ExportFormat::Sql { output, .. } => resolve(output),
+                ExportFormat::JsonSchema { out_dir, .. } => resolve(out_dir),
+            },
+            _ => {}
+        }
+    }
+}
+
 #[derive(Clone, Debug, ValueEnum)]
 enum ModelFormat {
+    /// A cuckoo JSON file
+    Json,
     /// A directory of JSON files
     Dir,
     /// A single binary file
     Bin,
 }
 
+impl ModelFormat {
+    fn format(&self) -> Format {
+        match self {
+            ModelFormat::Json => Format::Cuckoo,
+            ModelFormat::Dir => Format::Dir,
+            ModelFormat::Bin => Format::Bin,
+        }
+    }
+
+    /// The extension for a model in this format
+    ///
+    fn extension(&self) -> &'static str {
+        match self {
+            ModelFormat::Json => JSON_EXT,
+            ModelFormat::Dir => V2_EXT,
+            ModelFormat::Bin => BINCODE_EXT,
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum ImportSource {
+    /// SQL schema
+    ///
+    /// Tables become objects, columns become attributes, and foreign keys
+    /// become relationships. Objects are laid out on a grid.
+    Sql {
+        /// The schema, a file of `CREATE TABLE` statements
+        schema: PathBuf,
+        /// Domain name
+        #[arg(long, short)]
+        domain: String,
+        /// Module name
+        ///
+        /// This is the name of the module in sarzak.toml, and the name of the
+        /// Rust module. It defaults to the domain name.
+        #[arg(long, short)]
+        module: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ExportFormat {
+    /// GraphViz DOT
+    ///
+    /// Objects are nodes, listing their attributes, and relationships are
+    /// edges, labelled with their number and multiplicity.
+    Dot {
+        /// Domain name, or path to a model file
+        domain: String,
+        /// Output file
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Mermaid class diagram
+    ///
+    /// Objects are classes, listing their attributes. Relationships include
+    /// their phrases, and UML multiplicities. Supertypes and subtypes are
+    /// drawn as inheritance.
+    Mermaid {
+        /// Domain name, or path to a model file
+        domain: String,
+        /// Output file
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// PlantUML class diagram
+    ///
+    /// The same as the Mermaid diagram, with associative objects drawn as
+    /// association classes.
+    Plantuml {
+        /// Domain name, or path to a model file
+        domain: String,
+        /// Output file
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// SQL schema
+    ///
+    /// Objects are tables, and attributes are columns. Binary relationships are
+    /// foreign keys, and associative objects are join tables.
+    Sql {
+        /// Domain name, or path to a model file
+        domain: String,
+        /// SQL dialect
+        #[arg(long, short, value_enum, default_value_t = SqlDialect::Sqlite)]
+        dialect: SqlDialect,
+        /// Output file
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// JSON Schema
+    ///
+    /// A schema for each object, and one for the whole store, describing what
+    /// the generated serde code writes.
+    JsonSchema {
+        /// Domain name, or path to a model file
+        domain: String,
+        /// Output directory
+        ///
+        /// Every schema is written here. Without it, just the schema for the
+        /// store, which includes all of the others, is written to stdout.
+        #[arg(long, short)]
+        out_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Debug, ValueEnum)]
+enum SqlDialect {
+    Sqlite,
+    Postgres,
+}
+
+#[derive(Clone, Debug, ValueEnum)]
+enum DocFormat {
+    Html,
+    Markdown,
+}
+
+#[derive(Clone, Debug, ValueEnum)]
+enum ListFormat {
+    /// A table for humans
+    Table,
+    /// JSON for everyone else
+    Json,
+}
+
+/// What to do about generated files that have been edited by hand
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Overwrite {
+    Refuse,
+    Backup,
+    Force,
+}
+
+/// Options for `gen` that are passed along to each compiler invocation
+///
+#[derive(Clone, Copy, Debug)]
+struct GenerateOptions {
+    strict: bool,
+    overwrite: Overwrite,
+    /// The `[lint]` table from sarzak.toml
+    lint: LintConfig,
+}
+
 /// Compiler enum for parsing compiler options
 ///
 /// This is clumsy. Notice that the compiler options need to be buried in an
@@ -181,7 +718,10 @@ fn main() -> Result<()> {
         .with(EnvFilter::from_default_env())
         .init();
 
-    let args = Args::parse();
+    let mut args = Args::parse();
+    args.command.resolve_paths(
+        &std::env::current_dir().context("😱 unable to find the current directory")?,
+    );
 
     if args.test {
         println!("Running in test mode 🧪.");
@@ -195,31 +735,125 @@ fn main() -> Result<()> {
     }
 
     match args.command {
-        Command::New { domain, module } => execute_command_new(
+        Command::Init => execute_command_init(&args.package_dir, args.test)?,
+        Command::Doctor => execute_command_doctor(&args.package_dir)?,
+        Command::New {
+            domain,
+            module,
+            from,
+        } => execute_command_new(
             &domain,
             &module,
+            &from,
             &args.package_dir,
             args.test,
             args.verbosity,
         )?,
-        Command::Generate { compiler, modules } => execute_command_generate(
+        Command::Remove {
+            module,
+            keep_model,
+            yes,
+        } => execute_command_remove(&module, keep_model, yes, &args.package_dir, args.test)?,
+        Command::Rename {
+            old,
+            new,
+            module_only,
+            domain_only,
+        } => execute_command_rename(
+            &old,
+            &new,
+            !domain_only,
+            !module_only,
+            &args.package_dir,
+            args.test,
+        )?,
+        Command::List { format } => execute_command_list(&format, &args.package_dir)?,
+        Command::Generate {
+            compiler,
+            modules,
+            strict,
+            force,
+            backup,
+        } => execute_command_generate(
             &compiler,
             &modules,
+            &GenerateOptions {
+                strict,
+                overwrite: if force {
+                    Overwrite::Force
+                } else if backup {
+                    Overwrite::Backup
+                } else {
+                    Overwrite::Refuse
+                },
+                // This is filled in once we've read sarzak.toml.
+                lint: LintConfig::default(),
+            },
             &args.package_dir,
             args.test,
             args.verbosity,
         )?,
+        Command::Verify { modules } => execute_command_verify(&modules, &args.package_dir)?,
+        Command::Lint { modules } => execute_command_lint(&modules, &args.package_dir)?,
         Command::Convert {
             domain,
             format,
             out_dir,
+            all,
+            out_dir_flag,
+            output,
+            verify,
         } => {
-            // if let Some(out_dir) = out_dir {
-            execute_command_convert(&domain, &format, &args.package_dir, &out_dir)?
-            // } else {
-            //     execute_command_convert(&domain, &format, &args.package_dir)?
-            // }
+            let out_dir = out_dir.or(out_dir_flag);
+            match (all, domain, format) {
+                (Some(format), _, _) => execute_command_convert_all(
+                    &format,
+                    &args.package_dir,
+                    &out_dir,
+                    &output,
+                    verify,
+                )?,
+                (None, Some(domain), Some(format)) => execute_command_convert(
+                    &domain,
+                    &format,
+                    &args.package_dir,
+                    &out_dir,
+                    &output,
+                    verify,
+                )?,
+                // Clap makes sure that we have one or the other.
+                _ => unreachable!(),
+            }
         }
+        Command::Diff {
+            a,
+            b,
+            ignore_layout,
+        } => execute_command_diff(&a, &b, ignore_layout, &args.package_dir)?,
+        Command::Merge {
+            base,
+            ours,
+            theirs,
+            output,
+        } => execute_command_merge(&base, &ours, &theirs, &output)?,
+        Command::Fmt { modules, check } => {
+            execute_command_fmt(&modules, check, &args.package_dir, args.test)?
+        }
+        Command::InstallMergeDriver => {
+            execute_command_install_merge_driver(&args.package_dir, args.test)?
+        }
+        Command::Import { source } => {
+            execute_command_import(&source, &args.package_dir, args.test)?
+        }
+        Command::Export { format } => execute_command_export(&format, &args.package_dir)?,
+        Command::Render { domain, output } => {
+            execute_command_render(&domain, &output, &args.package_dir)?
+        }
+        Command::Doc {
+            domain,
+            format,
+            out_dir,
+        } => execute_command_doc(&domain, &format, &out_dir, &args.package_dir, args.test)?,
         #[cfg(feature = "gui")]
         Command::Show { domain } => execute_command_show(&domain, &args.package_dir, args.test)?,
     }
@@ -231,30 +865,7 @@ fn main() -> Result<()> {
 fn execute_command_show(domain: &str, dir: &Option<PathBuf>, test_mode: bool) -> Result<()> {
     let package_root = find_package_dir(dir)?;
 
-    let mut model_path = package_root.clone();
-    model_path.push(MODEL_DIR);
-    if domain.ends_with(JSON_EXT) {
-        model_path.push(domain);
-    } else {
-        model_path.push(format!("{}.{}", domain, JSON_EXT));
-    }
-
-    let model_path = if !model_path.exists() {
-        let mut model_path = package_root.clone();
-        if domain.ends_with(JSON_EXT) {
-            model_path.push(domain);
-        } else {
-            model_path.push(format!("{}.{}", domain, JSON_EXT));
-        }
-
-        if !model_path.exists() {
-            return Err(anyhow!("Unable to find model file for domain {}", domain));
-        } else {
-            model_path
-        }
-    } else {
-        model_path
-    };
+    let model_path = find_model(&package_root, domain)?;
 
     if test_mode {
         println!("Would open model file {}", model_path.display());
@@ -263,11 +874,7 @@ fn execute_command_show(domain: &str, dir: &Option<PathBuf>, test_mode: bool) ->
 
     println!("Opening model file {}", model_path.display());
 
-    let model = DomainBuilder::new()
-        .cuckoo_model(&model_path)
-        .context("😱 reading model file")?
-        .build_v2()
-        .context("😱 building domain")?;
+    let model = load_model(&model_path)?;
 
     sarzak_cli::boink::boink_main(model).map_err(|e| anyhow!("{}", e))
 }
@@ -277,268 +884,1759 @@ fn execute_command_convert(
     format: &ModelFormat,
     dir: &Option<PathBuf>,
     out_dir: &Option<PathBuf>,
+    output: &Option<String>,
+    verify: bool,
 ) -> Result<()> {
     let package_root = find_package_dir(dir)?;
 
-    let mut model_path = package_root.clone();
-    // model_path.push(MODEL_DIR);
+    let model_path = find_model(&package_root, domain)?;
+    let converted_name = output_path(&model_path, format, out_dir, output);
 
-    let mut converted_name = if let Some(out_dir) = out_dir {
-        out_dir.clone()
-    } else {
-        model_path.clone()
-    };
+    convert_model(&model_path, format, &converted_name, verify)?;
 
-    if domain.ends_with(JSON_EXT) {
-        model_path.push(domain);
-    } else {
-        model_path.push(format!("{}.{}", domain, JSON_EXT));
-    }
+    Ok(())
+}
 
-    let model_path = if !model_path.exists() {
-        let mut model_path = package_root.clone();
-        if domain.ends_with(JSON_EXT) {
-            model_path.push(domain);
-        } else {
-            model_path.push(format!("{}.{}", domain, JSON_EXT));
-        }
+/// Convert the model of every module
+///
+fn execute_command_convert_all(
+    format: &ModelFormat,
+    dir: &Option<PathBuf>,
+    out_dir: &Option<PathBuf>,
+    output: &Option<String>,
+    verify: bool,
+) -> Result<()> {
+    // Otherwise every model would be written to the same place.
+    if let Some(output) = output {
+        anyhow::ensure!(
+            output.contains(NAME_PLACEHOLDER),
+            format!(
+                "😱 --output must contain {} when converting with --all",
+                NAME_PLACEHOLDER
+            )
+        );
+    }
 
-        if !model_path.exists() {
-            return Err(anyhow!("Unable to find model file for domain {}", domain));
-        } else {
-            model_path
-        }
-    } else {
-        model_path
-    };
+    let package_root = find_package_dir(dir)?;
+    let config = load_config(&package_root)?;
 
-    println!("Opening model file {}", model_path.display());
+    let mut modules: Vec<_> = config.modules.iter().collect();
+    modules.sort_by(|a, b| a.0.cmp(b.0));
 
-    let model = DomainBuilder::new()
-        .cuckoo_model(&model_path)
-        .context("😱 reading model file")?
-        .build_v2()
-        .context("😱 building domain")?;
+    let mut converted = Vec::new();
+    let mut failed = Vec::new();
+    for (name, module) in modules {
+        println!("\nConverting module {}", Colour::Blue.paint(name));
 
-    let model_name = model_path.file_stem().unwrap().to_str().unwrap();
-    match format {
-        ModelFormat::Dir => {
-            let dir_model_name = format!("{}.v2.{}", model_name, JSON_EXT);
-            converted_name.push(dir_model_name);
+        let model_path = package_root.join(&module.model);
+        let converted_name = output_path(&model_path, format, out_dir, output);
 
-            model
-                .persist(&converted_name)
-                .context("😱 writing model file")
+        match convert_model(&model_path, format, &converted_name, verify) {
+            Ok(path) => converted.push((name, path)),
+            Err(e) => {
+                eprintln!("{}", Colour::Red.paint(format!("{:?}", e)));
+                failed.push(name);
+            }
         }
-        ModelFormat::Bin => {
-            let bincode_model_name = format!("{}.{}", model_name, BINCODE_EXT);
-            converted_name.push(bincode_model_name);
+    }
 
-            model
-                .persist_bincode(&converted_name)
-                .context("😱 writing model file")
-        }
+    println!();
+    for (name, path) in &converted {
+        println!("✅ {} ➡️  {}", Colour::Blue.paint(*name), path.display());
+    }
+    for name in &failed {
+        println!("❌ {}", Colour::Blue.paint(*name));
     }
 
-    // Check that the path exists, and that it's a file. From there we just
-    // have to trust...
+    anyhow::ensure!(
+        failed.is_empty(),
+        format!(
+            "😱 {} of {} model(s) failed to convert",
+            failed.len(),
+            failed.len() + converted.len()
+        )
+    );
+
+    println!(
+        "{}",
+        Colour::Green.paint(format!("\nConverted {} model(s)", converted.len()))
+    );
+
+    Ok(())
+}
+
+/// Convert a model file to another format
+///
+/// The converted model is written to `converted_name`, which is returned.
+fn convert_model(
+    model_path: &Path,
+    format: &ModelFormat,
+    converted_name: &Path,
+    verify: bool,
+) -> Result<PathBuf> {
+    println!("Opening model file {}", model_path.display());
+
+    let model = load_model(model_path)?;
 
-    //     anyhow::bail!(format!("😱 {:?} is not a json file!", model_file));
-    // }
+    let output_format = format.format();
+
+    anyhow::ensure!(
+        converted_name != model_path,
+        format!(
+            "😱 converting would overwrite {}; try another output directory",
+            model_path.display()
+        )
+    );
 
-    // // Here is where we can get the modification time of the model.
-    // let model_metadata = fs::metadata(model_file).context("😱 reading model metadata")?;
-    // let model_modified = model_metadata
-    //     .modified()
-    //     .context("😱 reading model modified time")?;
+    let view = view_model(&model, model_path)?;
 
-    // // We want to compare it to the modification time of the v2 model. Only
-    // // continue if the model is newer.
-    // // Ensure that we can find the models directory
-    // //
-    // let mut v2_model = root.clone();
-    // v2_model.push(MODEL_DIR);
-    // anyhow::ensure!(
-    //     v2_model.exists(),
-    //     format!("😱 Unable to find model directory: {}.", v2_model.display())
-    // );
-    // debug!("Found model ✈️  directory.");
+    println!("Writing model file {}", converted_name.display());
+    write_model(&model, &view, converted_name, output_format)?;
 
-    // let model_name = model_file.file_stem().unwrap().to_str().unwrap();
-    // let v2_model_name = format!("{}.v2.{}", model_name, JSON_EXT);
-    // v2_model.push(v2_model_name);
+    if verify {
+        let converted = load_model_as(converted_name, output_format)?;
+        let changes = diff_conversion(
+            &view,
+            &view_model(&converted, converted_name)?,
+            output_format,
+        );
 
-    // let model = if !v2_model.exists() {
-    //     // If the v2 model doesn't exist, we need to create it.
-    //     let model = DomainBuilder::new()
-    //         .cuckoo_model(&model_file)
-    //         .context("😱 reading model file")?
-    //         .build_v2()
-    //         .context("😱 building domain")?;
+        anyhow::ensure!(
+            changes.is_empty(),
+            format!(
+                "😱 the converted model differs from the original:\n{}",
+                changes
+                    .iter()
+                    .map(|c| format!("  {}", c))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            )
+        );
+        println!("{}", Colour::Green.paint("Verified 🎉"));
+    }
 
-    //     model.persist(v2_model).context("😱 persisting model")?;
+    Ok(converted_name.to_path_buf())
 }
 
-fn execute_command_new(
-    domain: &str,
-    module: &Option<String>,
+fn execute_command_diff(
+    a: &str,
+    b: &str,
+    ignore_layout: bool,
     dir: &Option<PathBuf>,
-    test_mode: bool,
-    _verbosity: u8,
 ) -> Result<()> {
-    let rust_name = domain.to_snake_case();
-    let module_name = match module {
-        Some(m) => m.to_snake_case(),
-        None => rust_name.clone(),
-    };
+    let a_view = load_model_spec(a, dir)?;
+    let b_view = load_model_spec(b, dir)?;
 
-    // Find the package root
-    //
-    let package_root = find_package_dir(dir)?;
+    println!(
+        "Comparing {} ➡️  {}\n",
+        Colour::Blue.paint(a),
+        Colour::Blue.paint(b)
+    );
 
-    // Update te config file
-    //
-    let mut config_path = package_root.clone();
-    config_path.push(SARZAK_CONFIG_TOML);
+    let (layout, changes): (Vec<_>, Vec<_>) = diff_views(&a_view, &b_view)
+        .into_iter()
+        .partition(|c| c.is_layout());
 
-    if !test_mode {
-        // We create the file here because below we open it for editing, and it's
-        // easier to create a file with the [domains] table.
-        if !config_path.exists() {
-            // Create the config file
-            debug!("💥 Creating {}.", SARZAK_CONFIG_TOML);
-            let mut config = File::create(&config_path)?;
-            config.write_all(b"[modules]")?;
+    for change in &changes {
+        println!("{}", change);
+    }
+
+    if !ignore_layout && !layout.is_empty() {
+        if !changes.is_empty() {
+            println!();
         }
+        println!("Layout:");
+        for change in &layout {
+            println!("{}", change);
+        }
+    }
 
-        let mut toml_string = String::new();
-        File::open(&config_path)
-            .context(format!(
-                "😱 unable to open configuration file: {}",
-                SARZAK_CONFIG_TOML
-            ))?
-            .read_to_string(&mut toml_string)?;
-        let mut config = toml_string.parse::<Table>()?;
-        let modules = config
-            .get_mut("modules")
-            .expect("There should be a [modules] table in the configuration.")
-            .as_table_mut()
-            .unwrap();
+    if changes.is_empty() && (ignore_layout || layout.is_empty()) {
+        println!("{}", Colour::Green.paint("No differences 🎉"));
+    } else if ignore_layout {
+        println!("\n{} change(s)", changes.len());
+    } else {
+        println!(
+            "\n{} change(s), {} layout change(s)",
+            changes.len(),
+            layout.len()
+        );
+    }
+
+    Ok(())
+}
+
+fn execute_command_merge(
+    base: &Path,
+    ours: &Path,
+    theirs: &Path,
+    output: &Option<PathBuf>,
+) -> Result<()> {
+    let base_model = cuckoo::read_model(base)?;
+    let our_model = cuckoo::read_model(ours)?;
+    let their_model = cuckoo::read_model(theirs)?;
+
+    let merge = merge_models(&base_model, &our_model, &their_model);
+    write_output(&cuckoo::format_model(&merge.model)?, output)?;
+
+    // The merged model may be on stdout, so this goes to stderr.
+    if merge.conflicts.is_empty() {
+        debug!("Merged {} cleanly", ours.display());
+        return Ok(());
+    }
+
+    eprintln!(
+        "{} {}:",
+        Colour::Red.paint("Conflicts merging"),
+        Colour::Blue.paint(ours.display().to_string())
+    );
+    for conflict in &merge.conflicts {
+        eprintln!("  ⚔️  {}: {}", conflict.subject, conflict.detail);
+    }
+    eprintln!(
+        "\n{} conflict(s). Our side was kept in each case.",
+        merge.conflicts.len()
+    );
+
+    std::process::exit(MERGE_CONFLICTS);
+}
+
+fn execute_command_fmt(
+    modules: &Option<Vec<String>>,
+    check: bool,
+    dir: &Option<PathBuf>,
+    test_mode: bool,
+) -> Result<()> {
+    let package_root = find_package_dir(dir)?;
+    let config = load_config(&package_root)?;
+
+    let mut names: Vec<&String> = match modules {
+        Some(modules) => modules.iter().filter(|m| !m.is_empty()).collect(),
+        None => config.modules.keys().collect(),
+    };
+    names.sort();
+
+    let mut count = 0;
+    for name in names {
+        let module = match config.modules.get(name) {
+            Some(module) => module,
+            None => {
+                eprintln!(
+                    "😱 No module named {} found in {}!",
+                    name, SARZAK_CONFIG_TOML
+                );
+                warn!("did not find {} in {}", name, SARZAK_CONFIG_TOML);
+                continue;
+            }
+        };
+
+        let model_path = package_root.join(&module.model);
+        if Format::detect(&model_path)? != Format::Cuckoo {
+            debug!("{} is not a cuckoo model, skipping", model_path.display());
+            continue;
+        }
+
+        let json = fs::read_to_string(&model_path).context(format!(
+            "😱 unable to read model file: {}",
+            model_path.display()
+        ))?;
+        let formatted = cuckoo::format_model(&cuckoo::parse_model(&json)?)?;
+
+        if formatted == json {
+            println!("✅ {}", Colour::Blue.paint(name));
+        } else if check || test_mode {
+            println!(
+                "✋ {} needs formatting: {}",
+                Colour::Blue.paint(name),
+                module.model.display()
+            );
+            count += 1;
+        } else {
+            println!(
+                "🎨 {} formatted: {}",
+                Colour::Blue.paint(name),
+                module.model.display()
+            );
+            write_atomic(&model_path, formatted)?;
+        }
+    }
+
+    if check && count > 0 {
+        println!(
+            "{}",
+            Colour::Red.paint(format!("\n{} model(s) need formatting", count))
+        );
+        std::process::exit(MODELS_UNFORMATTED);
+    }
+
+    Ok(())
+}
+
+fn execute_command_install_merge_driver(dir: &Option<PathBuf>, test_mode: bool) -> Result<()> {
+    let package_root = find_package_dir(dir)?;
+
+    println!(
+        "Installing the sarzak merge driver in {} 🔀",
+        Colour::Blue.paint(package_root.display().to_string())
+    );
+
+    for (key, value) in [
+        ("name", "sarzak model merge"),
+        ("driver", "sarzak merge %O %A %B -o %A"),
+    ] {
+        let key = format!("merge.{}.{}", MERGE_DRIVER, key);
+        println!("  ⚙️  Setting {} in .git/config.", key);
+        if !test_mode {
+            let output = process::Command::new("git")
+                .args(["config", "--local", &key, value])
+                .current_dir(&package_root)
+                .output()
+                .context("😱 unable to run git")?;
+            anyhow::ensure!(
+                output.status.success(),
+                format!(
+                    "😱 git config {} failed: {}",
+                    key,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )
+            );
+        }
+    }
+
+    let mut attributes = package_root.clone();
+    attributes.push(GIT_ATTRIBUTES);
+    let line = format!("{}/*.{} merge={}", MODEL_DIR, JSON_EXT, MERGE_DRIVER);
+    let contents = fs::read_to_string(&attributes).unwrap_or_default();
+    if contents.lines().any(|l| l.trim() == line) {
+        println!("  {} already has {}.", GIT_ATTRIBUTES, line);
+    } else {
+        println!("  📝 Adding {} to {}.", line, attributes.display());
+        if !test_mode {
+            let mut contents = contents;
+            if !contents.is_empty() && !contents.ends_with('\n') {
+                contents.push('\n');
+            }
+            contents.push_str(&line);
+            contents.push('\n');
+            fs::write(&attributes, contents)
+                .context(format!("😱 Failed to write to file: {:?}", attributes))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Load a model from a domain name, a path, or a git revision
+///
+/// A git revision is spelled `git:<rev>:<path>`, and `path` is handed to git
+/// as is, from the package directory. That makes it relative to the root of
+/// the repository, or, if it starts with `./`, to the package. The file is
+/// pulled out with `git show` into a temporary file, since that's what the
+/// model loader wants. Only model files will do; a directory model is many
+/// files, and git won't hand us those in one go.
+fn load_model_spec(spec: &str, dir: &Option<PathBuf>) -> Result<ModelView> {
+    if let Some(rest) = spec.strip_prefix(GIT_PREFIX) {
+        let (rev, path) = rest
+            .split_once(':')
+            .ok_or_else(|| anyhow!("😱 expected {}<rev>:<path>, but found {}", GIT_PREFIX, spec))?;
+        let package_root = find_package_dir(dir)?;
+        let object = format!("{}:{}", rev, path);
+
+        let kind = process::Command::new("git")
+            .args(["cat-file", "-t", &object])
+            .current_dir(&package_root)
+            .output()
+            .context("😱 unable to run git")?;
+        anyhow::ensure!(
+            String::from_utf8_lossy(&kind.stdout).trim() != "tree",
+            format!(
+                "😱 {} is a directory model, which can't be loaded from git; convert it to a model file first",
+                object
+            )
+        );
+
+        let output = process::Command::new("git")
+            .arg("show")
+            .arg(&object)
+            .current_dir(&package_root)
+            .output()
+            .context("😱 unable to run git")?;
+        anyhow::ensure!(
+            output.status.success(),
+            format!(
+                "😱 git show {}:{} failed: {}",
+                rev,
+                path,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+        );
+
+        // Keep the extension, so that we can tell what sort of model it is.
+        let mut temp = std::env::temp_dir();
+        temp.push(format!(
+            "sarzak-{}-{}",
+            process::id(),
+            rev.replace('/', "_")
+        ));
+        if let Some(ext) = Path::new(path).extension() {
+            temp.set_extension(ext);
+        }
+        write_atomic(&temp, &output.stdout)?;
+
+        let view = load_model(&temp).and_then(|model| view_model(&model, &temp));
+        let _ = fs::remove_file(&temp);
+
+        return view;
+    }
+
+    let path = Path::new(spec);
+    let model_path = if path.exists() {
+        path.to_path_buf()
+    } else {
+        let package_root = find_package_dir(dir)?;
+        find_model(&package_root, spec)?
+    };
+    debug!("Opening model file {}", model_path.display());
+
+    view_model(&load_model(&model_path)?, &model_path)
+}
+
+fn execute_command_import(
+    source: &ImportSource,
+    dir: &Option<PathBuf>,
+    test_mode: bool,
+) -> Result<()> {
+    match source {
+        ImportSource::Sql {
+            schema,
+            domain,
+            module,
+        } => {
+            // Read the schema before we go and change directories on ourselves.
+            let sql = fs::read_to_string(schema)
+                .context(format!("😱 unable to read schema: {}", schema.display()))?;
+            let (tables, mut notes) = import::sql::parse(&sql)?;
+            let (view, more_notes) = import::sql::to_view(&tables, domain);
+            notes.extend(more_notes);
+
+            println!("Read {} tables from {}", tables.len(), schema.display());
+            for note in &notes {
+                eprintln!("{}", Colour::Yellow.paint(format!("⚠️  {}", note)));
+            }
+
+            create_domain(domain, module, cuckoo::from_view(&view), dir, test_mode)
+        }
+    }
+}
+
+fn execute_command_export(format: &ExportFormat, dir: &Option<PathBuf>) -> Result<()> {
+    let package_root = find_package_dir(dir)?;
+
+    let (contents, output) = match format {
+        ExportFormat::Dot { domain, output } => {
+            let view = load_view(&package_root, domain)?;
+            (export::dot::to_dot(&view), output)
+        }
+        ExportFormat::Mermaid { domain, output } => {
+            let view = load_view(&package_root, domain)?;
+            (export::mermaid::to_mermaid(&view), output)
+        }
+        ExportFormat::Plantuml { domain, output } => {
+            let view = load_view(&package_root, domain)?;
+            (export::plantuml::to_plantuml(&view), output)
+        }
+        ExportFormat::Sql {
+            domain,
+            dialect,
+            output,
+        } => {
+            let view = load_view(&package_root, domain)?;
+            let dialect = match dialect {
+                SqlDialect::Sqlite => export::sql::Dialect::Sqlite,
+                SqlDialect::Postgres => export::sql::Dialect::Postgres,
+            };
+            (export::sql::to_sql(&view, dialect)?, output)
+        }
+        ExportFormat::JsonSchema { domain, out_dir } => {
+            let view = load_view(&package_root, domain)?;
+            let out_dir = match out_dir {
+                Some(out_dir) => out_dir,
+                None => {
+                    let store = export::json_schema::store_schema(&view);
+                    return write_output(&store.to_json()?, &None);
+                }
+            };
+
+            fs::create_dir_all(out_dir)
+                .context(format!("😱 unable to create {}", out_dir.display()))?;
+            let schemas = export::json_schema::to_json_schemas(&view);
+            for schema in &schemas {
+                write_output(&schema.to_json()?, &Some(out_dir.join(&schema.file_name)))?;
+            }
+
+            return Ok(());
+        }
+    };
+
+    write_output(&contents, output)
+}
+
+fn execute_command_render(
+    domain: &str,
+    output: &Option<PathBuf>,
+    dir: &Option<PathBuf>,
+) -> Result<()> {
+    let package_root = find_package_dir(dir)?;
+    let view = load_view(&package_root, domain)?;
+
+    write_output(&render::to_svg(&view), output)
+}
+
+fn execute_command_doc(
+    domain: &str,
+    format: &DocFormat,
+    out_dir: &Option<PathBuf>,
+    dir: &Option<PathBuf>,
+    test_mode: bool,
+) -> Result<()> {
+    let package_root = find_package_dir(dir)?;
+    let view = load_view(&package_root, domain)?;
+
+    let out_dir = match out_dir {
+        Some(out_dir) => out_dir.clone(),
+        None => {
+            let mut out_dir = package_root.clone();
+            out_dir.push(SCRATCH_DIR);
+            out_dir.push("doc");
+            out_dir.push(view.name.to_snake_case());
+            out_dir
+        }
+    };
+
+    let format = match format {
+        DocFormat::Html => doc::Format::Html,
+        DocFormat::Markdown => doc::Format::Markdown,
+    };
+    let pages = doc::document(&view, format);
+
+    if test_mode {
+        for page in &pages {
+            println!("Would write {}", out_dir.join(&page.file_name).display());
+        }
+        return Ok(());
+    }
+
+    for page in &pages {
+        let path = out_dir.join(&page.file_name);
+        // Object pages are in a directory of their own.
+        let dir = path.parent().unwrap_or(&out_dir);
+        fs::create_dir_all(dir).context(format!("😱 unable to create {}", dir.display()))?;
+        write_atomic(&path, &page.contents)?;
+    }
+
+    println!(
+        "{}",
+        Colour::Green.paint(format!(
+            "Wrote {} pages to {}",
+            pages.len(),
+            out_dir.display()
+        ))
+    );
+
+    Ok(())
+}
+
+/// Load a domain's model, and take a look at it
+///
+fn load_view(package_root: &Path, domain: &str) -> Result<ModelView> {
+    let model_path = find_model(package_root, domain)?;
+    debug!("Opening model file {}", model_path.display());
+
+    let model = load_model(&model_path)?;
+
+    view_model(&model, &model_path)
+}
+
+/// Write to a file, if there is one, or stdout
+///
+fn write_output(contents: &str, output: &Option<PathBuf>) -> Result<()> {
+    match output {
+        Some(path) => {
+            write_atomic(path, contents)?;
+            eprintln!("Wrote {}", Colour::Blue.paint(path.display().to_string()));
+        }
+        None => print!("{}", contents),
+    }
+
+    Ok(())
+}
+
+/// Find the model file for a domain
+///
+/// The domain may be a name, or a path. We look in the models directory first,
+/// and then in the package root. A name without an extension is taken to be a
+/// cuckoo JSON file.
+fn find_model(package_root: &Path, domain: &str) -> Result<PathBuf> {
+    let file_name = match Path::new(domain).extension().and_then(|ext| ext.to_str()) {
+        Some(JSON_EXT) | Some(BINCODE_EXT) => domain.to_owned(),
+        _ => format!("{}.{}", domain, JSON_EXT),
+    };
+
+    let mut model_path = package_root.to_path_buf();
+    model_path.push(MODEL_DIR);
+    model_path.push(&file_name);
+    if model_path.exists() {
+        return Ok(model_path);
+    }
+
+    let model_path = package_root.join(&file_name);
+    if model_path.exists() {
+        Ok(model_path)
+    } else {
+        Err(anyhow!("Unable to find model file for domain {}", domain))
+    }
+}
+
+/// Figure out where a converted model goes
+///
+/// An explicit `output` wins, and is either a path, or a template containing
+/// `{name}` and `{ext}`. Otherwise the converted model is named after the
+/// original, and written to `out_dir`, or next to the original.
+fn output_path(
+    model_path: &Path,
+    format: &ModelFormat,
+    out_dir: &Option<PathBuf>,
+    output: &Option<String>,
+) -> PathBuf {
+    // A v2 directory is named `foo.v2.json`, and we just want the `foo`.
+    let model_name = model_path.file_stem().unwrap().to_str().unwrap();
+    let model_name = model_name.strip_suffix(".v2").unwrap_or(model_name);
+
+    let expand = |template: &str| {
+        template
+            .replace(NAME_PLACEHOLDER, model_name)
+            .replace(EXT_PLACEHOLDER, format.extension())
+    };
+
+    match (output, out_dir) {
+        (Some(output), _) => expand(output).into(),
+        (None, Some(out_dir)) => out_dir.join(expand(DEFAULT_OUTPUT_TEMPLATE)),
+        (None, None) => model_path.with_file_name(expand(DEFAULT_OUTPUT_TEMPLATE)),
+    }
+}
+
+/// Write a model in a particular format
+///
+/// Cuckoo models are written from `view`, so that they're drawn as they were.
+fn write_model(model: &Domain, view: &ModelView, path: &Path, format: Format) -> Result<()> {
+    match format {
+        Format::Cuckoo => {
+            let json = cuckoo::format_model(&cuckoo::from_view(view))?;
+            write_atomic(path, json)
+        }
+        Format::Dir => replace_atomic(path, |path| {
+            model.persist(path).context("😱 writing model file")
+        }),
+        Format::Bin => replace_atomic(path, |path| {
+            model.persist_bincode(path).context("😱 writing model file")
+        }),
+    }
+}
+
+fn execute_command_init(dir: &Option<PathBuf>, test_mode: bool) -> Result<()> {
+    let package_root = find_package_dir(dir)?;
+
+    println!(
+        "Initializing sarzak in {}❗️",
+        package_root.to_string_lossy()
+    );
+
+    let mut config_path = package_root.clone();
+    config_path.push(SARZAK_CONFIG_TOML);
+    if config_path.exists() {
+        println!("  {} already exists.", SARZAK_CONFIG_TOML);
+    } else {
+        println!("  💥 Creating {}.", config_path.display());
+        if !test_mode {
+            write_atomic(&config_path, CONFIG_TEMPLATE)?;
+        }
+    }
+
+    let mut model_dir = package_root.clone();
+    model_dir.push(MODEL_DIR);
+    if model_dir.exists() {
+        println!("  {} already exists.", model_dir.display());
+    } else {
+        println!("  📁 Creating {}.", model_dir.display());
+        if !test_mode {
+            fs::create_dir_all(&model_dir)
+                .context(format!("😱 Failed to create directory: {:?}", model_dir))?;
+        }
+    }
+
+    // The cached v2 models are derived from the model files, and don't belong
+    // in git.
+    //
+    let mut gitignore = package_root.clone();
+    gitignore.push(".gitignore");
+    let ignore = format!("/{}/*.v2.{}", MODEL_DIR, JSON_EXT);
+    let contents = fs::read_to_string(&gitignore).unwrap_or_default();
+    if contents.lines().any(|line| line.trim() == ignore) {
+        println!("  .gitignore already ignores {}.", ignore);
+    } else {
+        println!("  🙈 Adding {} to {}.", ignore, gitignore.display());
+        if !test_mode {
+            let mut contents = contents;
+            if !contents.is_empty() && !contents.ends_with('\n') {
+                contents.push('\n');
+            }
+            contents.push_str(&ignore);
+            contents.push('\n');
+            fs::write(&gitignore, contents)
+                .context(format!("😱 Failed to write to file: {:?}", gitignore))?;
+        }
+    }
+
+    println!("\nChecking the environment 🩺");
+    report_checkups(&check_environment(&package_root));
+
+    Ok(())
+}
+
+fn execute_command_doctor(dir: &Option<PathBuf>) -> Result<()> {
+    println!("This is sarzak {} 🩺", env!("CARGO_PKG_VERSION"));
+    for (name, (version, revision)) in [
+        ("sarzak", SARZAK_VERSION),
+        ("grace", GRACE_VERSION),
+        ("nut", NUT_VERSION),
+    ] {
+        println!("  {:7} {}", name, describe_version(version, revision));
+    }
+
+    let mut checkups = Vec::new();
+
+    // If cargo is missing we can't very well ask it where the package is.
+    //
+    let package_root = match process::Command::new("cargo").arg("--version").output() {
+        Ok(output) if output.status.success() => {
+            checkups.push(Checkup::good(format!(
+                "found {}",
+                String::from_utf8_lossy(&output.stdout).trim()
+            )));
+            find_package_dir(dir)?
+        }
+        _ => {
+            checkups.push(Checkup::bad("cargo not found; is it in your path?"));
+            match dir {
+                Some(dir) => dir.clone(),
+                None => std::env::current_dir()?,
+            }
+        }
+    };
+
+    println!("\nChecking {} 🔬", package_root.display());
+
+    // Compare our versions with the package's.
+    //
+    match find_lockfile(&package_root) {
+        Some(lock_path) => {
+            let lock = fs::read_to_string(&lock_path)
+                .context(format!("😱 unable to read {}", lock_path.display()))?;
+            match Lockfile::parse(&lock) {
+                Ok(lock) => {
+                    for (name, (version, revision)) in
+                        [("sarzak", SARZAK_VERSION), ("grace", GRACE_VERSION)]
+                    {
+                        checkups.push(compare_versions(&lock, name, version, revision));
+                    }
+                }
+                Err(e) => checkups.push(Checkup::bad(format!(
+                    "unable to parse {}: {}",
+                    lock_path.display(),
+                    e
+                ))),
+            }
+        }
+        None => checkups.push(Checkup::warning(
+            "no Cargo.lock found; run `cargo generate-lockfile` to compare versions",
+        )),
+    }
+
+    checkups.extend(check_environment(&package_root));
+
+    // Look for the models.
+    //
+    if let Ok(config) = load_config(&package_root) {
+        let mut modules: Vec<_> = config.modules.iter().collect();
+        modules.sort_by_key(|(name, _)| *name);
+        for (name, module_config) in modules {
+            let mut model_file = package_root.clone();
+            model_file.push(&module_config.model);
+            if model_file.exists() {
+                checkups.push(Checkup::good(format!(
+                    "found model for {}: {}",
+                    name,
+                    module_config.model.display()
+                )));
+            } else {
+                checkups.push(Checkup::bad(format!(
+                    "missing model for {}: {}",
+                    name,
+                    model_file.display()
+                )));
+            }
+        }
+    }
+
+    report_checkups(&checkups);
+
+    if checkups.iter().any(|c| c.health == Health::Bad) {
+        println!("\n{}", Colour::Red.paint("Not healthy 🤒"));
+    } else {
+        println!("\n{}", Colour::Green.paint("All good 🥳"));
+    }
+
+    Ok(())
+}
+
+fn describe_version(version: &str, revision: &str) -> String {
+    if revision.is_empty() {
+        version.to_owned()
+    } else {
+        format!("{} ({})", version, &revision[..revision.len().min(8)])
+    }
+}
+
+/// Compare a dependency of ours with the package's
+///
+/// A different version is bad. The same version from a different git revision
+/// is probably bad, but it's not certain.
+fn compare_versions(lock: &Lockfile, name: &str, version: &str, revision: &str) -> Checkup {
+    match lock.find(name) {
+        Some(package) if package.version != version => Checkup::bad(format!(
+            "package uses {} {}, but we were built with {}",
+            name,
+            package.describe(),
+            describe_version(version, revision)
+        )),
+        Some(package) => match package.revision() {
+            Some(rev) if !revision.is_empty() && rev != revision => Checkup::warning(format!(
+                "package uses {} {}, but we were built with {}",
+                name,
+                package.describe(),
+                describe_version(version, revision)
+            )),
+            _ => Checkup::good(format!("package uses {} {}", name, package.describe())),
+        },
+        None => Checkup::good(format!("package does not depend on {}", name)),
+    }
+}
+
+/// Find the Cargo.lock for a package
+///
+/// In a workspace it lives in the workspace root, so we look up the tree.
+fn find_lockfile(package_root: &Path) -> Option<PathBuf> {
+    package_root
+        .ancestors()
+        .map(|dir| dir.join("Cargo.lock"))
+        .find(|path| path.exists())
+}
+
+fn execute_command_new(
+    domain: &str,
+    module: &Option<String>,
+    from: &Option<PathBuf>,
+    dir: &Option<PathBuf>,
+    test_mode: bool,
+    _verbosity: u8,
+) -> Result<()> {
+    // The namespace is shared between the model and the module file.
+    //
+    let domain_ns = cuckoo::domain_namespace(domain);
+
+    // Read the model we are forking before we go and change directories on
+    // ourselves.
+    //
+    let model = match from {
+        Some(from) => {
+            let from = fs::canonicalize(from)
+                .context(format!("😱 unable to find model file: {}", from.display()))?;
+            debug!("Creating model 🐶 from {:?}.", from);
+            let mut model = cuckoo::read_model(from)?;
+            cuckoo::rebase_ids(&mut model, &domain_ns);
+            model
+        }
+        None => {
+            debug!("Creating blank model 🐶.");
+            cuckoo::parse_model(BLANK_MODEL)?
+        }
+    };
+
+    create_domain(domain, module, model, dir, test_mode)
+}
+
+/// Add a domain to the package
+///
+/// The model is written to the models directory, the module is registered in
+/// sarzak.toml, and the module file is created.
+fn create_domain(
+    domain: &str,
+    module: &Option<String>,
+    mut model: serde_json::Value,
+    dir: &Option<PathBuf>,
+    test_mode: bool,
+) -> Result<()> {
+    let rust_name = domain.to_snake_case();
+    let module_name = match module {
+        Some(m) => m.to_snake_case(),
+        None => rust_name.clone(),
+    };
+
+    // Find the package root
+    //
+    let package_root = find_package_dir(dir)?;
+
+    // Update te config file
+    //
+    let mut config_path = package_root.clone();
+    config_path.push(SARZAK_CONFIG_TOML);
+
+    if !test_mode {
+        // We create the file here because below we open it for editing, and it's
+        // easier to create a file with the [domains] table.
+        if !config_path.exists() {
+            // Create the config file
+            debug!("💥 Creating {}.", SARZAK_CONFIG_TOML);
+            write_atomic(&config_path, CONFIG_TEMPLATE)?;
+        }
+
+        let mut config = read_config_table(&config_path)?;
+        let modules = config
+            .get_mut("modules")
+            .expect("There should be a [modules] table in the configuration.")
+            .as_table_mut()
+            .unwrap();
+
+        // Check to see if domain already exists
+        //
+        match &modules.get(&module_name) {
+            Some(_) => {
+                let missive = format!(
+                    "😱 module '{}' already exists in the configuration!",
+                    rust_name
+                );
+                error!("{}", &missive);
+                eprintln!("{}", missive);
+                std::process::exit(MODULE_EXISTS);
+            }
+            None => {}
+        }
+
+        let options = CompilerOptions::Grace(GraceCompilerOptions::default());
+        let module_config = ModuleConfig {
+            model: format!("models/{}.{}", rust_name, JSON_EXT).into(),
+            compiler: vec![options],
+        };
+
+        modules.insert(module_name.clone(), Value::try_from(module_config).unwrap());
+
+        write_config_table(&config_path, &config)?;
+    }
+
+    println!(
+        "Creating new domain ✨{}✨ in {}❗️",
+        domain,
+        package_root.to_string_lossy()
+    );
+    println!("The module will be called ✨{}✨.", module_name);
+
+    // Write a blank model file.
+    //
+    let mut model_file = package_root.clone();
+    model_file.push(MODEL_DIR);
+
+    // Make sure the directory exists.
+    //
+    fs::create_dir_all(&model_file).context(format!("😱 Failed to create models directory."))?;
+
+    // Interesting aside. PathBuf::set_file_name does a pop first.
+    model_file.push("fubar");
+
+    model_file.set_file_name(&rust_name);
+    model_file.set_extension(JSON_EXT);
+
+    // The namespace is shared between the model and the module file.
+    //
+    let domain_ns = cuckoo::domain_namespace(domain);
+    debug!("Creating model 🐶 file at {:?}.", model_file);
+    cuckoo::set_paper(&mut model, domain, &domain_ns);
+    let model = cuckoo::format_model(&model)?;
+
+    if !test_mode {
+        write_atomic(&model_file, model.as_bytes())?;
+    }
+
+    // Create a new directory for the module
+    //
+    let mut src_dir = package_root.clone();
+    src_dir.push("src");
+    src_dir.push(&module_name);
+    debug!("Creating module directory {:?}.", src_dir);
+    if !test_mode {
+        fs::create_dir(&src_dir)
+            .context(format!("😱 Failed to create directory: {:?}", src_dir))?;
+    }
+
+    // Generate a "module" .rs file
+    //
+    debug!("Creating {}.rs. 🥳", module_name);
+    src_dir.set_file_name(&module_name);
+    src_dir.set_extension("rs");
+
+    if !test_mode {
+        let contents = generate_module_file(domain, &domain_ns);
+        write_atomic(&src_dir, contents.as_bytes())?;
+    }
+
+    // Update `lib.rs` with the new module.
+    //
+    // I wonder is there's a way to parse the file as rust code, edit
+    // the tokenstream, and then write it back out? Nicely formatted?
+    //
+    // Thinking that this waits. There are issues to overcome. The first
+    // is that we can't include the new module because it has no source
+    // files. We can't generate source files until we have a model.
+    // At least that's how the code gen code works now. They all fail
+    // (panic) trying to read objects. In any case, code gen should
+    // happen first.
+
+    Ok(())
+}
+
+fn execute_command_remove(
+    module: &str,
+    keep_model: bool,
+    yes: bool,
+    dir: &Option<PathBuf>,
+    test_mode: bool,
+) -> Result<()> {
+    let package_root = find_package_dir(dir)?;
+
+    let mut config_path = package_root.clone();
+    config_path.push(SARZAK_CONFIG_TOML);
+
+    let mut config = read_config_table(&config_path)?;
+    let modules = config
+        .get_mut("modules")
+        .and_then(|m| m.as_table_mut())
+        .ok_or_else(|| anyhow!("😱 no [modules] table in {}", SARZAK_CONFIG_TOML))?;
+
+    let module_config: ModuleConfig = match modules.get(module) {
+        Some(m) => m.clone().try_into()?,
+        None => {
+            let missive = format!(
+                "😱 No module named {} found in {}!",
+                module, SARZAK_CONFIG_TOML
+            );
+            error!("{}", &missive);
+            eprintln!("{}", missive);
+            std::process::exit(NOTHING_TO_DO);
+        }
+    };
+
+    // Sort out everything that we'd like to get rid of.
+    //
+    let mut model_file = package_root.clone();
+    model_file.push(&module_config.model);
+
+    let mut v2_model = package_root.clone();
+    v2_model.push(MODEL_DIR);
+    if let Some(stem) = module_config.model.file_stem() {
+        v2_model.push(format!("{}.v2.{}", stem.to_string_lossy(), JSON_EXT));
+    }
+
+    let mut src_dir = package_root.clone();
+    src_dir.push("src");
+    src_dir.push(module);
+
+    let mut module_file = src_dir.clone();
+    module_file.set_extension("rs");
+
+    // The model may be a directory, if it's a v2 model.
+    let mut doomed = Vec::new();
+    if !keep_model {
+        doomed.push(model_file);
+    }
+    doomed.extend([v2_model, src_dir, module_file]);
+    doomed.retain(|path| path.exists());
+
+    let crate_root = find_crate_root(&package_root);
+
+    println!(
+        "Removing module ✨{}✨ from {}.",
+        module,
+        package_root.to_string_lossy()
+    );
+    for path in &doomed {
+        println!("  🗑  {}", path.display());
+    }
+    if let Some(crate_root) = &crate_root {
+        println!("  ✂️  `mod {};` from {}", module, crate_root.display());
+    }
+
+    if test_mode {
+        println!("Would remove {} from {}.", module, SARZAK_CONFIG_TOML);
+        return Ok(());
+    }
 
-        // Check to see if domain already exists
-        //
-        match &modules.get(&module_name) {
-            Some(_) => {
-                let missive = format!(
-                    "😱 module '{}' already exists in the configuration!",
-                    rust_name
-                );
-                error!("{}", &missive);
-                eprintln!("{}", missive);
-                std::process::exit(MODULE_EXISTS);
-            }
-            None => {}
+    if !yes && !confirm("Are you sure?")? {
+        println!("Nothing removed.");
+        return Ok(());
+    }
+
+    modules.remove(module);
+    write_config_table(&config_path, &config)?;
+
+    for path in &doomed {
+        debug!("Removing {:?}.", path);
+        if path.is_dir() {
+            fs::remove_dir_all(path)
+                .context(format!("😱 Failed to remove directory: {:?}", path))?;
+        } else {
+            fs::remove_file(path).context(format!("😱 Failed to remove file: {:?}", path))?;
         }
+    }
 
-        let options = CompilerOptions::Grace(GraceCompilerOptions::default());
-        let module_config = ModuleConfig {
-            model: format!("models/{}.{}", rust_name, JSON_EXT).into(),
-            compiler: vec![options],
-        };
+    if let Some(crate_root) = crate_root {
+        remove_mod_declaration(&crate_root, module)?;
+    }
 
-        modules.insert(module_name.clone(), Value::try_from(module_config).unwrap());
+    println!("Removed module ✨{}✨.", module);
 
-        let mut toml_file = File::create(&config_path).context(format!(
-            "😱 unable to open {} for writing",
-            SARZAK_CONFIG_TOML
-        ))?;
-        toml_file
-            .write_all(config.to_string().as_bytes())
-            .context(format!("😱 unable to write {}!", SARZAK_CONFIG_TOML))?;
+    Ok(())
+}
+
+fn execute_command_rename(
+    old: &str,
+    new: &str,
+    rename_module: bool,
+    rename_domain: bool,
+    dir: &Option<PathBuf>,
+    test_mode: bool,
+) -> Result<()> {
+    let rust_name = new.to_snake_case();
+    let old_module = old.to_owned();
+    let new_module = if rename_module {
+        rust_name.clone()
+    } else {
+        old_module.clone()
+    };
+
+    let package_root = find_package_dir(dir)?;
+
+    let mut config_path = package_root.clone();
+    config_path.push(SARZAK_CONFIG_TOML);
+
+    let mut config = read_config_table(&config_path)?;
+    let modules = config
+        .get_mut("modules")
+        .and_then(|m| m.as_table_mut())
+        .ok_or_else(|| anyhow!("😱 no [modules] table in {}", SARZAK_CONFIG_TOML))?;
+
+    let mut module_config: ModuleConfig = match modules.get(&old_module) {
+        Some(m) => m.clone().try_into()?,
+        None => {
+            let missive = format!(
+                "😱 No module named {} found in {}!",
+                old_module, SARZAK_CONFIG_TOML
+            );
+            error!("{}", &missive);
+            eprintln!("{}", missive);
+            std::process::exit(NOTHING_TO_DO);
+        }
+    };
+
+    if rename_module && old_module != new_module && modules.contains_key(&new_module) {
+        let missive = format!(
+            "😱 module '{}' already exists in the configuration!",
+            new_module
+        );
+        error!("{}", &missive);
+        eprintln!("{}", missive);
+        std::process::exit(MODULE_EXISTS);
     }
 
     println!(
-        "Creating new domain ✨{}✨ in {}❗️",
-        domain,
+        "Renaming ✨{}✨ to ✨{}✨ in {}❗️",
+        old_module,
+        new,
         package_root.to_string_lossy()
     );
-    println!("The module will be called ✨{}✨.", module_name);
 
-    // Write a blank model file.
+    // The domain: the model file, and the name on the paper.
     //
-    let mut model_file = package_root.clone();
-    model_file.push(MODEL_DIR);
+    if rename_domain {
+        let mut old_model = package_root.clone();
+        old_model.push(&module_config.model);
+
+        let new_model_path: PathBuf = format!("{}/{}.{}", MODEL_DIR, rust_name, JSON_EXT).into();
+        let mut new_model = package_root.clone();
+        new_model.push(&new_model_path);
+
+        if old_model != new_model {
+            anyhow::ensure!(
+                !new_model.exists(),
+                format!("😱 model file {} already exists", new_model.display())
+            );
+        }
 
-    // Make sure the directory exists.
-    //
-    fs::create_dir_all(&model_file).context(format!("😱 Failed to create models directory."))?;
+        println!("  📝 {} ➡️  {}", old_model.display(), new_model.display());
 
-    // Interesting aside. PathBuf::set_file_name does a pop first.
-    model_file.push("fubar");
+        if !test_mode {
+            let mut model = cuckoo::read_model(&old_model)?;
+            let domain_ns =
+                cuckoo::paper_namespace(&model).unwrap_or_else(|| cuckoo::domain_namespace(new));
+            cuckoo::set_paper(&mut model, new, &domain_ns);
 
-    model_file.set_file_name(&rust_name);
-    model_file.set_extension(JSON_EXT);
+            write_atomic(&new_model, cuckoo::format_model(&model)?)?;
+            if old_model != new_model {
+                fs::remove_file(&old_model)
+                    .context(format!("😱 Failed to remove file: {:?}", old_model))?;
+            }
+        }
 
-    debug!("Creating blank model 🐶 file at {:?}.", model_file);
-    if !test_mode {
-        let model = BLANK_MODEL.replace("Paper::blank", &domain);
-        File::create(&model_file)
-            .context(format!("😱 Failed to create file: {:?}", model_file))?
-            .write_all(model.as_bytes())
-            .context(format!("😱 Failed to write to file: {:?}", model_file))?;
+        // The cached v2 model has the old name baked in. It'll get rebuilt the
+        // next time we generate code.
+        let mut v2_model = package_root.clone();
+        v2_model.push(MODEL_DIR);
+        if let Some(stem) = module_config.model.file_stem() {
+            v2_model.push(format!("{}.v2.{}", stem.to_string_lossy(), JSON_EXT));
+        }
+        if v2_model.exists() {
+            println!("  🗑  {}", v2_model.display());
+            if !test_mode {
+                fs::remove_dir_all(&v2_model)
+                    .context(format!("😱 Failed to remove directory: {:?}", v2_model))?;
+            }
+        }
+
+        module_config.model = new_model_path;
     }
 
-    // Create a new directory for the module
+    // The module: sarzak.toml, the source, and paths into it.
     //
-    let mut src_dir = package_root.clone();
-    src_dir.push("src");
-    src_dir.push(&module_name);
-    debug!("Creating module directory {:?}.", src_dir);
-    if !test_mode {
-        fs::create_dir(&src_dir)
-            .context(format!("😱 Failed to create directory: {:?}", src_dir))?;
+    let mut old_src = package_root.clone();
+    old_src.push("src");
+    old_src.push(&old_module);
+    let mut old_module_file = old_src.clone();
+    old_module_file.set_extension("rs");
+
+    let mut new_src = package_root.clone();
+    new_src.push("src");
+    new_src.push(&new_module);
+    let mut new_module_file = new_src.clone();
+    new_module_file.set_extension("rs");
+
+    if rename_module && old_module != new_module {
+        for (from, to) in [(&old_src, &new_src), (&old_module_file, &new_module_file)] {
+            if from.exists() {
+                anyhow::ensure!(!to.exists(), format!("😱 {} already exists", to.display()));
+                println!("  📦 {} ➡️  {}", from.display(), to.display());
+                if !test_mode {
+                    fs::rename(from, to)
+                        .context(format!("😱 Failed to rename {:?} to {:?}", from, to))?;
+                }
+            }
+        }
+
+        if let Some(crate_root) = find_crate_root(&package_root) {
+            println!(
+                "  ✂️  `mod {};` ➡️  `mod {};` in {}",
+                old_module,
+                new_module,
+                crate_root.display()
+            );
+            if !test_mode {
+                rename_mod_declaration(&crate_root, &old_module, &new_module)?;
+            }
+        }
+
+        // Fix up paths into the module.
+        //
+        let prefixes = ["crate".to_owned(), crate_name(&package_root)?];
+
+        let mut sources = Vec::new();
+        for dir in ["src", "tests", "examples", "benches"] {
+            collect_rust_files(&package_root.join(dir), &mut sources)?;
+        }
+
+        for source in sources {
+            let contents = fs::read_to_string(&source)
+                .context(format!("😱 Failed to read file: {:?}", source))?;
+            let mut updated = contents.clone();
+            for prefix in &prefixes {
+                updated = rename_path_segment(
+                    &updated,
+                    &format!("{}::{}", prefix, old_module),
+                    &format!("{}::{}", prefix, new_module),
+                );
+                updated = rename_in_use_groups(&updated, prefix, &old_module, &new_module);
+            }
+            if updated != contents {
+                println!("  🔧 {}", source.display());
+                if !test_mode {
+                    write_atomic(&source, updated)?;
+                }
+            }
+        }
+
+        modules.remove(&old_module);
     }
 
-    // Generate a "module" .rs file
+    // Fix the title in the module file that `new` created.
     //
-    debug!("Creating {}.rs. 🥳", module_name);
-    src_dir.set_file_name(&module_name);
-    src_dir.set_extension("rs");
+    if rename_domain {
+        let module_file = if test_mode {
+            &old_module_file
+        } else {
+            &new_module_file
+        };
+        if module_file.exists() {
+            let contents = fs::read_to_string(module_file)
+                .context(format!("😱 Failed to read file: {:?}", module_file))?;
+            let title = format!("//! {} Domain", new.to_title_case());
+            let mut lines: Vec<&str> = contents.lines().collect();
+            if let Some(first) = lines.first_mut() {
+                if first.starts_with("//! ") && first.ends_with(" Domain") {
+                    *first = &title;
+                    let mut contents = lines.join("\n");
+                    contents.push('\n');
+                    println!("  🔧 {}", module_file.display());
+                    if !test_mode {
+                        write_atomic(module_file, contents)?;
+                    }
+                }
+            }
+        }
+    }
 
-    if !test_mode {
-        let contents = generate_module_file(&domain);
-        File::create(&src_dir)
-            .context(format!("😱 Failed to create file: {:?}", src_dir))?
-            .write_all(contents.as_bytes())
-            .context(format!("😱 Failed to write to file: {:?}", src_dir))?;
+    modules.insert(new_module.clone(), Value::try_from(module_config)?);
+
+    if test_mode {
+        println!("Would update {}.", SARZAK_CONFIG_TOML);
+    } else {
+        write_config_table(&config_path, &config)?;
     }
 
-    // Update `lib.rs` with the new module.
-    //
-    // I wonder is there's a way to parse the file as rust code, edit
-    // the tokenstream, and then write it back out? Nicely formatted?
-    //
-    // Thinking that this waits. There are issues to overcome. The first
-    // is that we can't include the new module because it has no source
-    // files. We can't generate source files until we have a model.
-    // At least that's how the code gen code works now. They all fail
-    // (panic) trying to read objects. In any case, code gen should
-    // happen first.
+    println!("The module is called ✨{}✨.", new_module);
+
+    Ok(())
+}
+
+/// The state of a module, as reported by `list`
+///
+#[derive(Debug, Serialize)]
+struct ModuleStatus {
+    module: String,
+    model: PathBuf,
+    model_exists: bool,
+    compilers: Vec<String>,
+    /// `None` if there is no cached model
+    cache_fresh: Option<bool>,
+    /// `None` if there is no generated code
+    code_stale: Option<bool>,
+}
+
+fn execute_command_list(format: &ListFormat, dir: &Option<PathBuf>) -> Result<()> {
+    let package_root = find_package_dir(dir)?;
+    let config = load_config(&package_root)?;
+
+    let mut names: Vec<&String> = config.modules.keys().collect();
+    names.sort();
+
+    let mut statuses = Vec::new();
+    for name in names {
+        let module_config = &config.modules[name];
+
+        let mut model_file = package_root.clone();
+        model_file.push(&module_config.model);
+        let model_modified = fs::metadata(&model_file).and_then(|m| m.modified()).ok();
+
+        let mut metadata_path = package_root.clone();
+        metadata_path.push(MODEL_DIR);
+        if let Some(stem) = module_config.model.file_stem() {
+            metadata_path.push(format!("{}.v2.{}", stem.to_string_lossy(), JSON_EXT));
+        }
+        metadata_path.push(METADATA_FILE);
+        let cache_modified = fs::metadata(&metadata_path).and_then(|m| m.modified()).ok();
+
+        let mut src_dir = package_root.clone();
+        src_dir.push("src");
+        src_dir.push(name);
+        let code_modified = newest_modified(&src_dir)?;
+
+        let compilers = module_config
+            .compiler
+            .iter()
+            .map(|compiler| match compiler {
+                CompilerOptions::Grace(_) => "grace".to_owned(),
+            })
+            .collect();
+
+        statuses.push(ModuleStatus {
+            module: name.clone(),
+            model: module_config.model.clone(),
+            model_exists: model_modified.is_some(),
+            compilers,
+            cache_fresh: match (model_modified, cache_modified) {
+                (Some(model), Some(cache)) => Some(cache >= model),
+                (None, Some(_)) => Some(true),
+                (_, None) => None,
+            },
+            code_stale: match (model_modified, code_modified) {
+                (Some(model), Some(code)) => Some(code < model),
+                (None, Some(_)) => Some(false),
+                (_, None) => None,
+            },
+        });
+    }
+
+    match format {
+        ListFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&statuses)?);
+        }
+        ListFormat::Table => {
+            let yes_no = |b: bool| if b { "yes" } else { "no" };
+            let headers = ["module", "model", "exists", "compilers", "cache", "code"];
+            let rows: Vec<[String; 6]> = statuses
+                .iter()
+                .map(|status| {
+                    [
+                        status.module.clone(),
+                        status.model.display().to_string(),
+                        yes_no(status.model_exists).to_owned(),
+                        status.compilers.join(","),
+                        match status.cache_fresh {
+                            Some(true) => "fresh",
+                            Some(false) => "stale",
+                            None => "-",
+                        }
+                        .to_owned(),
+                        match status.code_stale {
+                            Some(true) => "stale",
+                            Some(false) => "fresh",
+                            None => "-",
+                        }
+                        .to_owned(),
+                    ]
+                })
+                .collect();
+
+            let mut widths = headers.map(|h| h.len());
+            for row in &rows {
+                for (width, cell) in widths.iter_mut().zip(row) {
+                    *width = (*width).max(cell.chars().count());
+                }
+            }
+
+            let line = |cells: &[String]| {
+                cells
+                    .iter()
+                    .zip(widths)
+                    .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                    .collect::<Vec<_>>()
+                    .join("  ")
+                    .trim_end()
+                    .to_owned()
+            };
+
+            println!(
+                "{}",
+                Colour::Blue.paint(line(&headers.map(|h| h.to_owned())))
+            );
+            for row in &rows {
+                println!("{}", line(row));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Find the most recent modification time of any file in a directory
+///
+fn newest_modified(dir: &Path) -> Result<Option<time::SystemTime>> {
+    if !dir.is_dir() {
+        return Ok(None);
+    }
+
+    let mut newest = None;
+    for entry in fs::read_dir(dir).context(format!("😱 Failed to read directory: {:?}", dir))? {
+        let path = entry?.path();
+        let modified = if path.is_dir() {
+            newest_modified(&path)?
+        } else {
+            Some(fs::metadata(&path)?.modified()?)
+        };
+        newest = newest.max(modified);
+    }
+
+    Ok(newest)
+}
+
+/// Recursively collect the Rust source files in a directory
+///
+fn collect_rust_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(dir).context(format!("😱 Failed to read directory: {:?}", dir))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_rust_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Replace a path, but only when it's a whole path segment
+///
+/// Renaming `crate::foo` shouldn't also rename `crate::foo_bar`.
+fn rename_path_segment(source: &str, from: &str, to: &str) -> String {
+    let mut result = String::with_capacity(source.len());
+    let mut rest = source;
+
+    while let Some(index) = rest.find(from) {
+        let (before, after) = rest.split_at(index);
+        let after = &after[from.len()..];
+
+        let preceded = before
+            .chars()
+            .last()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_');
+        let followed = after
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_');
+
+        result.push_str(before);
+        if preceded || followed {
+            result.push_str(from);
+        } else {
+            result.push_str(to);
+        }
+        rest = after;
+    }
+    result.push_str(rest);
+
+    result
+}
+
+/// Rename a module where it's named in a grouped `use`
+///
+/// That's `old` in `use prefix::{old, ...}`, or `old::Foo` in the same place.
+/// Only the top level of the group is considered, since anything deeper isn't
+/// directly under `prefix`.
+fn rename_in_use_groups(source: &str, prefix: &str, old: &str, new: &str) -> String {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let opener = format!("{}::{{", prefix);
+    let mut result = String::with_capacity(source.len());
+    let mut rest = source;
+
+    while let Some(index) = rest.find(&opener) {
+        let (before, after) = rest.split_at(index);
+        result.push_str(before);
+        result.push_str(&opener);
+        let body = &after[opener.len()..];
+
+        // Something like `other_crate::{`.
+        if before.chars().last().is_some_and(is_ident) {
+            rest = body;
+            continue;
+        }
+
+        let mut depth = 1;
+        let mut item_start = true;
+        let mut i = 0;
+        while let Some(c) = body[i..].chars().next() {
+            if item_start
+                && depth == 1
+                && body[i..].starts_with(old)
+                && !body[i + old.len()..].chars().next().is_some_and(is_ident)
+            {
+                result.push_str(new);
+                i += old.len();
+                item_start = false;
+                continue;
+            }
+
+            result.push(c);
+            i += c.len_utf8();
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                ',' if depth == 1 => item_start = true,
+                c if c.is_whitespace() => {}
+                _ => item_start = false,
+            }
+        }
+        rest = &body[i..];
+    }
+    result.push_str(rest);
+
+    result
+}
+
+/// The name that other code uses for this package's library
+///
+/// This is `[lib] name`, if there is one, and otherwise the package name with
+/// hyphens turned into underscores. Cargo knows, so we ask it.
+fn crate_name(package_root: &Path) -> Result<String> {
+    let manifest = package_root.join("Cargo.toml");
+    let output = process::Command::new("cargo")
+        .args(["metadata", "--no-deps", "--format-version", "1"])
+        .arg("--manifest-path")
+        .arg(&manifest)
+        .output()
+        .context("😱 unable to run cargo metadata")?;
+    anyhow::ensure!(
+        output.status.success(),
+        format!(
+            "😱 cargo metadata failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+    );
+
+    let metadata: serde_json::Value =
+        serde_json::from_slice(&output.stdout).context("😱 unable to parse cargo metadata")?;
+    let manifest = fs::canonicalize(&manifest).unwrap_or(manifest);
+    let package = metadata["packages"]
+        .as_array()
+        .and_then(|packages| {
+            packages.iter().find(|p| {
+                p["manifest_path"]
+                    .as_str()
+                    .is_some_and(|m| fs::canonicalize(m).is_ok_and(|m| m == manifest))
+            })
+        })
+        .ok_or_else(|| anyhow!("😱 no package found in {}", manifest.display()))?;
+
+    let lib = package["targets"].as_array().and_then(|targets| {
+        targets.iter().find(|t| {
+            t["kind"]
+                .as_array()
+                .is_some_and(|kinds| kinds.iter().any(|k| k == "lib"))
+        })
+    });
+    let name = lib
+        .and_then(|lib| lib["name"].as_str())
+        .or_else(|| package["name"].as_str())
+        .unwrap_or_default();
+
+    Ok(name.replace('-', "_"))
+}
+
+/// Find the crate root
+///
+/// We look for `lib.rs` first, and then `main.rs`.
+fn find_crate_root(package_root: &Path) -> Option<PathBuf> {
+    ["lib.rs", "main.rs"]
+        .iter()
+        .map(|file| package_root.join("src").join(file))
+        .find(|path| path.exists())
+}
+
+/// Remove the `mod` declaration for a module from a source file
+///
+/// This is strictly line based, so we catch `mod foo;` and `pub mod foo;`, and
+/// nothing fancier.
+fn remove_mod_declaration(source: &Path, module: &str) -> Result<()> {
+    let contents =
+        fs::read_to_string(source).context(format!("😱 Failed to read file: {:?}", source))?;
+
+    let decl = format!("mod {};", module);
+    let mut removed = false;
+    let mut lines = Vec::new();
+    for line in contents.lines() {
+        let trimmed = line.trim();
+        if trimmed == decl || trimmed.strip_prefix("pub ") == Some(decl.as_str()) {
+            removed = true;
+        } else {
+            lines.push(line);
+        }
+    }
+
+    if removed {
+        debug!("Removing `{}` from {:?}.", decl, source);
+        let mut contents = lines.join("\n");
+        contents.push('\n');
+        fs::write(source, contents).context(format!("😱 Failed to write file: {:?}", source))?;
+    }
+
+    Ok(())
+}
+
+/// Rename the `mod` declaration for a module in a source file
+///
+/// Like `remove_mod_declaration`, this is line based.
+fn rename_mod_declaration(source: &Path, old: &str, new: &str) -> Result<()> {
+    let contents =
+        fs::read_to_string(source).context(format!("😱 Failed to read file: {:?}", source))?;
+
+    let old_decl = format!("mod {};", old);
+    let new_decl = format!("mod {};", new);
+    let mut renamed = false;
+    let mut lines = Vec::new();
+    for line in contents.lines() {
+        let trimmed = line.trim();
+        if trimmed == old_decl || trimmed.strip_prefix("pub ") == Some(old_decl.as_str()) {
+            renamed = true;
+            lines.push(line.replace(&old_decl, &new_decl));
+        } else {
+            lines.push(line.to_owned());
+        }
+    }
+
+    if renamed {
+        debug!("Renaming `{}` in {:?}.", old_decl, source);
+        let mut contents = lines.join("\n");
+        contents.push('\n');
+        fs::write(source, contents).context(format!("😱 Failed to write file: {:?}", source))?;
+    }
 
     Ok(())
 }
 
+/// Ask the user a yes or no question
+///
+fn confirm(prompt: &str) -> Result<bool> {
+    print!("{} [y/N] ", prompt);
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes" | "Yes"))
+}
+
 /// Generate a <domain>.rs file
 ///
 /// I guess this would have made a good template.
 ///
 /// This needs to be moved to the compiler. It should be responsible for creating
 /// _all_ source code in it's module.
-fn generate_module_file(domain: &str) -> String {
+fn generate_module_file(domain: &str, domain_ns: &Uuid) -> String {
     let mut context = CachingContext::new();
 
-    let uuid = Uuid::new_v5(&Uuid::NAMESPACE_OID, domain.as_bytes());
-
     emitln!(context, "//! {} Domain", domain.to_title_case());
     emitln!(context, "//!");
     emitln!(
@@ -557,7 +2655,11 @@ fn generate_module_file(domain: &str) -> String {
     emitln!(context, "pub use macros::*;");
     emitln!(context, "");
     emitln!(context, "// {}", domain);
-    emitln!(context, "pub const UUID_NS: Uuid = uuid!(\"{}\");", uuid);
+    emitln!(
+        context,
+        "pub const UUID_NS: Uuid = uuid!(\"{}\");",
+        domain_ns
+    );
     emitln!(context, "");
     emitln!(context, "#[cfg(test)]");
     emitln!(context, "mod tests {");
@@ -576,6 +2678,7 @@ fn generate_module_file(domain: &str) -> String {
 fn execute_command_generate(
     compiler: &Option<Compiler>,
     modules: &Option<Vec<String>>,
+    options: &GenerateOptions,
     package_dir: &Option<PathBuf>,
     test_mode: bool,
     verbosity: u8,
@@ -586,17 +2689,55 @@ fn execute_command_generate(
 
     // Open the config file
     //
-    let mut config_path = package_root.clone();
-    config_path.push(SARZAK_CONFIG_TOML);
-
-    let mut toml = String::new();
     // 🚧 We should be able to do something without a config file.
-    File::open(&config_path)
-        .context(format!("😱 unable to open {}", SARZAK_CONFIG_TOML))?
-        .read_to_string(&mut toml)?;
+    let config = load_config(&package_root)?;
+    let options = &GenerateOptions {
+        lint: config.lint,
+        ..*options
+    };
 
-    let config: Config = toml::from_str(&toml)?;
-    debug!("Loaded config 📝 file.");
+    // Keep other `gen`s out of our way.
+    //
+    let mut lock_path = package_root.clone();
+    lock_path.push(SCRATCH_DIR);
+    lock_path.push(LOCK_FILE);
+    let lock = if test_mode {
+        None
+    } else {
+        Some(Lock::acquire(&lock_path)?)
+    };
+
+    // A SIGINT lets the current module finish, and then we stop. A second one
+    // stops us right away.
+    //
+    ctrlc::set_handler(move || {
+        if !INTERRUPTED.swap(true, Ordering::SeqCst) {
+            eprintln!(
+                "{}",
+                Colour::Yellow.paint("\n✋ Interrupted, stopping after this module.")
+            );
+        } else {
+            let restored = active_snapshot().take().map(|snapshot| snapshot.restore());
+            let missive = match restored {
+                Some(Ok(())) => {
+                    "\n🛑 Aborting. The module being generated was put back the way it was."
+                        .to_owned()
+                }
+                Some(Err(e)) => format!(
+                    "\n🛑 Aborting. The module being generated couldn't be put back ({}); it \
+                     may be half written, so run gen again to fix it.",
+                    e
+                ),
+                None => "\n🛑 Aborting.".to_owned(),
+            };
+            eprintln!("{}", Colour::Red.paint(missive));
+            if !test_mode {
+                let _ = fs::remove_file(&lock_path);
+            }
+            std::process::exit(ABORTED);
+        }
+    })
+    .context("😱 unable to set SIGINT handler")?;
 
     // Process modules passed in on the command line.
     let mut count = 0;
@@ -614,6 +2755,10 @@ fn execute_command_generate(
         debug!("Found model ✈️  directory.");
 
         for module in modules {
+            if INTERRUPTED.load(Ordering::SeqCst) {
+                break;
+            }
+
             // Spaces between commas in the module specification result in spaces
             // in our domains list. Just skip.
             // Last time I put spaces in the list, the parser failed. So this is wonky.
@@ -641,6 +2786,7 @@ fn execute_command_generate(
                                 &package_root,
                                 &model_file,
                                 test_mode,
+                                options,
                                 &module,
                                 verbosity,
                             )?,
@@ -667,6 +2813,7 @@ fn execute_command_generate(
                                     &package_root,
                                     &model_file,
                                     test_mode,
+                                    options,
                                     &module,
                                     verbosity,
                                 )?
@@ -696,10 +2843,15 @@ fn execute_command_generate(
             );
             warn!("empty domains in {}", SARZAK_CONFIG_TOML);
 
+            drop(lock);
             std::process::exit(NOTHING_TO_DO);
         }
         // Iterate over all of the modules files in the config
         for (module, config) in &config.modules {
+            if INTERRUPTED.load(Ordering::SeqCst) {
+                break;
+            }
+
             let mut model_file = package_root.clone();
             model_file.push(&config.model);
 
@@ -709,6 +2861,7 @@ fn execute_command_generate(
                     &package_root,
                     &model_file,
                     test_mode,
+                    options,
                     &module,
                     verbosity,
                 )?
@@ -728,6 +2881,7 @@ fn execute_command_generate(
                         &package_root,
                         &model_file,
                         test_mode,
+                        options,
                         &module,
                         verbosity,
                     )?;
@@ -765,6 +2919,7 @@ fn invoke_model_compiler(
     root: &PathBuf,
     model_file: &PathBuf,
     test_mode: bool,
+    options: &GenerateOptions,
     module: &str,
     _verbosity: u8,
 ) -> Result<usize> {
@@ -774,25 +2929,212 @@ fn invoke_model_compiler(
         model_file.display(),
         module
     );
-    // Check that the path exists, and that it's a file. From there we just
-    // have to trust...
+    // Check that the path exists. From there we just have to trust...
     anyhow::ensure!(
         model_file.exists(),
         format!("😱 Model file ({:?}) does not exist!", model_file)
     );
+
+    let model = load_module_model(root, model_file, module)?;
+
+    // Lint first, and only say something if there's something to say.
+    let findings = lint_model(&model, &options.lint);
+    if !findings.is_empty() {
+        report_findings(module, &findings);
+    }
     anyhow::ensure!(
-        model_file.is_file(),
-        format!("😱 {:?} is not a model file!", model_file)
+        !findings.iter().any(|f| f.is_error()),
+        format!("😱 {} has lint errors, not generating code for it", module)
     );
-    if let Some(extension) = model_file.extension() {
-        anyhow::ensure!(
-            extension == JSON_EXT,
-            format!("😱 {:?} is not a json file!", model_file)
-        );
+
+    let mut src_path = root.clone();
+    src_path.push("src");
+
+    let mut module_dir = src_path.clone();
+    module_dir.push(module);
+    let manifest = Manifest::load(&module_dir)?;
+    if let Some(manifest) = &manifest {
+        check_generator(manifest, module, options.strict)?;
+        check_hand_edits(manifest, &module_dir, module, options.overwrite, test_mode)?;
+    }
+
+    println!(
+        "\nGenerating 🧬 code for module `{}` from domain {}!",
+        Colour::Blue.paint(module),
+        Colour::Yellow.paint(model_file.file_stem().unwrap().to_str().unwrap())
+    );
+    debug!("Generating 🧬 code for domain, {}!", model_file.display());
+
+    // If the compiler falls over part way through, we put things back the way
+    // they were.
+    //
+    let snapshot = if test_mode {
+        None
+    } else {
+        let mut store = root.clone();
+        store.push(SCRATCH_DIR);
+        store.push(SNAPSHOT_DIR);
+        store.push(module);
+
+        let mut module_file = module_dir.clone();
+        module_file.set_extension("rs");
+
+        Some(Snapshot::take(store, &[module_dir.clone(), module_file])?)
+    };
+
+    // So that we know which files this compiler wrote.
+    //
+    let before = if test_mode {
+        BTreeMap::new()
     } else {
-        anyhow::bail!(format!("😱 {:?} is not a json file!", model_file));
+        hash_files(&module_dir)?
+    };
+
+    // Where the SIGINT handler can find it.
+    *active_snapshot() = snapshot;
+
+    let result = match compiler {
+        Compiler::Grace { options } => {
+            let compiler = grace::ModelCompiler::default();
+            compiler
+                .compile(
+                    model,
+                    &root.file_stem().unwrap().to_str().unwrap(),
+                    &module,
+                    &src_path,
+                    Box::new(options),
+                    test_mode,
+                    // verbosity,
+                )
+                .map_err(anyhow::Error::msg)
+        } // Compiler::Dwarf { options } => {
+          //     invoke_dwarf(&options, &root, &model_file, test_mode, &module)
+          //         .map_err(anyhow::Error::msg)
+          // }
+    };
+
+    let count = match (result, active_snapshot().take()) {
+        (Ok(count), Some(snapshot)) => {
+            snapshot.discard()?;
+            count
+        }
+        (Ok(count), None) => count,
+        (Err(e), Some(snapshot)) => {
+            eprintln!(
+                "{}",
+                Colour::Red.paint(format!("⏪ Rolling back module `{}`.", module))
+            );
+            snapshot.restore()?;
+            return Err(e);
+        }
+        (Err(e), None) => return Err(e),
+    };
+
+    if !test_mode && module_dir.is_dir() {
+        // Other compilers may have generated files in here too.
+        let mut manifest = manifest.unwrap_or_default();
+        manifest.generator = Generator {
+            sarzak_cli: env!("CARGO_PKG_VERSION").to_owned(),
+            grace: GRACE_VERSION.0.to_owned(),
+            grace_revision: GRACE_VERSION.1.to_owned(),
+            model_hash: hash_path(model_file)?,
+        };
+        manifest.record_files(&module_dir, &before)?;
+        manifest.save(&module_dir)?;
+    }
+
+    Ok(count)
+}
+
+/// The snapshot of the module being generated
+///
+/// A poisoned lock still holds a perfectly good snapshot.
+fn active_snapshot() -> MutexGuard<'static, Option<Snapshot>> {
+    SNAPSHOT.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Load the model for a module, whatever format it's in
+///
+fn load_module_model(root: &Path, model_file: &Path, module: &str) -> Result<Domain> {
+    match Format::detect(model_file)? {
+        Format::Cuckoo => {
+            check_namespace(root, model_file, module)?;
+            load_cached_model(root, model_file)
+        }
+        format => load_model_as(model_file, format),
+    }
+}
+
+fn lint_model(model: &Domain, config: &LintConfig) -> Vec<Finding> {
+    lint(&ModelView::new(model), config)
+}
+
+fn report_findings(module: &str, findings: &[Finding]) {
+    if findings.is_empty() {
+        println!("✅ {}", Colour::Blue.paint(module));
+        return;
+    }
+
+    let errors = findings.iter().filter(|f| f.is_error()).count();
+    println!(
+        "{} {} ({} error(s), {} warning(s))",
+        if errors > 0 { "❌" } else { "⚠️ " },
+        Colour::Blue.paint(module),
+        errors,
+        findings.len() - errors
+    );
+    for finding in findings {
+        println!("    {}", finding);
+    }
+}
+
+fn execute_command_lint(modules: &Option<Vec<String>>, dir: &Option<PathBuf>) -> Result<()> {
+    let package_root = find_package_dir(dir)?;
+    let config = load_config(&package_root)?;
+
+    let mut names: Vec<&String> = match modules {
+        Some(modules) => modules.iter().filter(|m| !m.is_empty()).collect(),
+        None => config.modules.keys().collect(),
+    };
+    names.sort();
+
+    let mut errors = 0;
+    for name in names {
+        let module = match config.modules.get(name) {
+            Some(module) => module,
+            None => {
+                eprintln!(
+                    "😱 No module named {} found in {}!",
+                    name, SARZAK_CONFIG_TOML
+                );
+                warn!("did not find {} in {}", name, SARZAK_CONFIG_TOML);
+                continue;
+            }
+        };
+
+        let model_file = package_root.join(&module.model);
+        let model = load_module_model(&package_root, &model_file, name)?;
+        let findings = lint_model(&model, &config.lint);
+        report_findings(name, &findings);
+        errors += findings.iter().filter(|f| f.is_error()).count();
+    }
+
+    if errors > 0 {
+        println!(
+            "{}",
+            Colour::Red.paint(format!("\n{} lint error(s)", errors))
+        );
+        std::process::exit(LINT_ERRORS);
     }
 
+    Ok(())
+}
+
+/// Load a cuckoo model, by way of its cached v2 model
+///
+/// Building a domain from a cuckoo model is slow, so we keep a v2 copy in the
+/// models directory. It's rebuilt whenever the cuckoo model is newer.
+fn load_cached_model(root: &Path, model_file: &Path) -> Result<Domain> {
     // Here is where we can get the modification time of the model.
     let model_metadata = fs::metadata(model_file).context("😱 reading model metadata")?;
     let model_modified = model_metadata
@@ -803,7 +3145,7 @@ fn invoke_model_compiler(
     // continue if the model is newer.
     // Ensure that we can find the models directory
     //
-    let mut v2_model = root.clone();
+    let mut v2_model = root.to_path_buf();
     v2_model.push(MODEL_DIR);
     anyhow::ensure!(
         v2_model.exists(),
@@ -815,17 +3157,15 @@ fn invoke_model_compiler(
     let v2_model_name = format!("{}.v2.{}", model_name, JSON_EXT);
     v2_model.push(v2_model_name);
 
-    let model = if !v2_model.exists() {
+    if !v2_model.exists() {
         // If the v2 model doesn't exist, we need to create it.
-        let model = DomainBuilder::new()
-            .cuckoo_model(&model_file)
-            .context("😱 reading model file")?
-            .build_v2()
-            .context("😱 building domain")?;
+        let model = load_model_as(model_file, Format::Cuckoo)?;
 
-        model.persist(v2_model).context("😱 persisting model")?;
+        replace_atomic(&v2_model, |path| {
+            model.persist(path).context("😱 persisting model")
+        })?;
 
-        model
+        Ok(model)
     } else {
         let mut metadata_path = v2_model.clone();
         metadata_path.push(METADATA_FILE);
@@ -838,50 +3178,372 @@ fn invoke_model_compiler(
             .context("😱 reading v2 model modified time")?;
 
         if model_modified > v2_model_modified {
-            let model = DomainBuilder::new()
-                .cuckoo_model(&model_file)
-                .context("😱 reading model file")?
-                .build_v2()
-                .context("😱 building domain")?;
+            let model = load_model_as(model_file, Format::Cuckoo)?;
 
-            // 🚧 This is great for adding files, but how do we remove them?
-            model.persist(v2_model).context("😱 persisting model")?;
+            // Replacing the whole directory takes care of removing files
+            // that are no longer in the model.
+            replace_atomic(&v2_model, |path| {
+                model.persist(path).context("😱 persisting model")
+            })?;
 
-            model
+            Ok(model)
         } else {
-            Domain::load(&v2_model).context(format!("😱 loading model: {}", v2_model.display()))?
+            load_model_as(&v2_model, Format::Dir)
         }
+    }
+}
+
+/// Check what last generated a module
+///
+/// If it was a newer sarzak or grace than us, then regenerating is likely to
+/// undo something. We warn, or in strict mode, refuse.
+fn check_generator(manifest: &Manifest, module: &str, strict: bool) -> Result<()> {
+    let newer = |theirs: &str, ours: &str| match (
+        semver::Version::parse(theirs),
+        semver::Version::parse(ours),
+    ) {
+        (Ok(theirs), Ok(ours)) => theirs > ours,
+        _ => false,
     };
 
-    let mut src_path = root.clone();
-    src_path.push("src");
+    let mut complaints = Vec::new();
+    if newer(&manifest.generator.sarzak_cli, env!("CARGO_PKG_VERSION")) {
+        complaints.push(format!(
+            "sarzak {} (we are {})",
+            manifest.generator.sarzak_cli,
+            env!("CARGO_PKG_VERSION")
+        ));
+    }
+    if newer(&manifest.generator.grace, GRACE_VERSION.0) {
+        complaints.push(format!(
+            "grace {} (we have {})",
+            manifest.generator.grace, GRACE_VERSION.0
+        ));
+    }
 
-    println!(
-        "\nGenerating 🧬 code for module `{}` from domain {}!",
-        Colour::Blue.paint(module),
-        Colour::Yellow.paint(model_file.file_stem().unwrap().to_str().unwrap())
+    if complaints.is_empty() {
+        return Ok(());
+    }
+
+    let missive = format!(
+        "module `{}` was last generated by a newer {}",
+        module,
+        complaints.join(" and ")
     );
-    debug!("Generating 🧬 code for domain, {}!", model_file.display());
+    if strict {
+        anyhow::bail!("😱 {}; refusing to generate code in strict mode.", missive);
+    }
+    eprintln!("{}", Colour::Yellow.paint(format!("⚠️  {}", missive)));
+    warn!("{}", missive);
 
-    match compiler {
-        Compiler::Grace { options } => {
-            let compiler = grace::ModelCompiler::default();
-            compiler
-                .compile(
-                    model,
-                    &root.file_stem().unwrap().to_str().unwrap(),
-                    &module,
-                    &src_path,
-                    Box::new(options),
-                    test_mode,
-                    // verbosity,
-                )
-                .map_err(anyhow::Error::msg)
-        } // Compiler::Dwarf { options } => {
-          //     invoke_dwarf(&options, &root, &model_file, test_mode, &module)
-          //         .map_err(anyhow::Error::msg)
-          // }
+    Ok(())
+}
+
+/// How healthy something is
+///
+#[derive(Debug, PartialEq)]
+enum Health {
+    Good,
+    Warning,
+    Bad,
+}
+
+/// The result of checking one thing
+///
+#[derive(Debug)]
+struct Checkup {
+    health: Health,
+    message: String,
+}
+
+impl Checkup {
+    fn good<S: Into<String>>(message: S) -> Self {
+        Checkup {
+            health: Health::Good,
+            message: message.into(),
+        }
+    }
+
+    fn warning<S: Into<String>>(message: S) -> Self {
+        Checkup {
+            health: Health::Warning,
+            message: message.into(),
+        }
+    }
+
+    fn bad<S: Into<String>>(message: S) -> Self {
+        Checkup {
+            health: Health::Bad,
+            message: message.into(),
+        }
+    }
+}
+
+fn report_checkups(checkups: &[Checkup]) {
+    for checkup in checkups {
+        match checkup.health {
+            Health::Good => println!("  ✅ {}", checkup.message),
+            Health::Warning => println!("  ⚠️  {}", Colour::Yellow.paint(&checkup.message)),
+            Health::Bad => println!("  ❌ {}", Colour::Red.paint(&checkup.message)),
+        }
+    }
+}
+
+/// Check that the package can build generated code
+///
+/// We already know that cargo is around, since that's how we found the package.
+fn check_environment(package_root: &Path) -> Vec<Checkup> {
+    let mut checkups = Vec::new();
+
+    match process::Command::new("rustfmt").arg("--version").output() {
+        Ok(output) if output.status.success() => checkups.push(Checkup::good(format!(
+            "found {}",
+            String::from_utf8_lossy(&output.stdout).trim()
+        ))),
+        _ => checkups.push(Checkup::warning(
+            "rustfmt not found; generated code won't be formatted",
+        )),
+    }
+
+    let mut cargo_toml = package_root.to_path_buf();
+    cargo_toml.push("Cargo.toml");
+    let manifest = fs::read_to_string(&cargo_toml)
+        .ok()
+        .and_then(|toml| toml.parse::<Table>().ok());
+    match manifest {
+        Some(manifest) => {
+            let dependencies = manifest.get("dependencies").and_then(|d| d.as_table());
+            // Generated code leans on these.
+            for dependency in ["serde", "uuid"] {
+                if dependencies.is_some_and(|d| d.contains_key(dependency)) {
+                    checkups.push(Checkup::good(format!("package depends on {}", dependency)));
+                } else {
+                    checkups.push(Checkup::warning(format!(
+                        "package does not depend on {}, and generated code will",
+                        dependency
+                    )));
+                }
+            }
+        }
+        None => checkups.push(Checkup::bad(format!(
+            "unable to read {}",
+            cargo_toml.display()
+        ))),
+    }
+
+    let mut config_path = package_root.to_path_buf();
+    config_path.push(SARZAK_CONFIG_TOML);
+    if config_path.exists() {
+        match load_config(package_root) {
+            Ok(_) => checkups.push(Checkup::good(format!("{} is valid", SARZAK_CONFIG_TOML))),
+            Err(e) => checkups.push(Checkup::bad(format!(
+                "unable to load {}: {}",
+                SARZAK_CONFIG_TOML, e
+            ))),
+        }
+    } else {
+        checkups.push(Checkup::warning(format!(
+            "{} does not exist",
+            SARZAK_CONFIG_TOML
+        )));
+    }
+
+    checkups
+}
+
+/// Load the config file
+///
+fn load_config(package_root: &Path) -> Result<Config> {
+    let mut config_path = package_root.to_path_buf();
+    config_path.push(SARZAK_CONFIG_TOML);
+
+    let mut toml = String::new();
+    File::open(&config_path)
+        .context(format!("😱 unable to open {}", SARZAK_CONFIG_TOML))?
+        .read_to_string(&mut toml)?;
+
+    let config: Config = toml::from_str(&toml)?;
+    debug!("Loaded config 📝 file.");
+
+    Ok(config)
+}
+
+/// Read the config file as a TOML table
+///
+/// This is for when we need to edit the config file, and would rather not
+/// lose anything we don't know about along the way.
+fn read_config_table(config_path: &Path) -> Result<Table> {
+    let mut toml_string = String::new();
+    File::open(config_path)
+        .context(format!(
+            "😱 unable to open configuration file: {}",
+            SARZAK_CONFIG_TOML
+        ))?
+        .read_to_string(&mut toml_string)?;
+
+    Ok(toml_string.parse::<Table>()?)
+}
+
+/// Write the config file from a TOML table
+///
+fn write_config_table(config_path: &Path, config: &Table) -> Result<()> {
+    write_atomic(config_path, config.to_string())
+        .context(format!("😱 unable to write {}!", SARZAK_CONFIG_TOML))
+}
+
+/// Compare the model's namespace with the module's
+///
+/// The paper in the model has a `domain_ns`, and the module file generated by
+/// `new` has a `UUID_NS`. If they disagree then the UUIDs in the generated code
+/// won't match the model's. We just warn, since it's been this way for a while
+/// in some packages.
+fn check_namespace(root: &Path, model_file: &Path, module: &str) -> Result<()> {
+    let model = cuckoo::read_model(model_file)?;
+    let model_ns = match cuckoo::paper_namespace(&model) {
+        Some(ns) => ns,
+        None => {
+            debug!("No domain namespace in {}.", model_file.display());
+            return Ok(());
+        }
+    };
+
+    let mut module_file = root.to_path_buf();
+    module_file.push("src");
+    module_file.push(format!("{}.rs", module));
+
+    let module_ns = match fs::read_to_string(&module_file) {
+        Ok(contents) => contents.lines().find_map(|line| {
+            let line = line.trim();
+            if line.starts_with("pub const UUID_NS") {
+                let start = line.find("uuid!(\"")? + 7;
+                let end = start + line[start..].find('"')?;
+                Uuid::parse_str(&line[start..end]).ok()
+            } else {
+                None
+            }
+        }),
+        Err(_) => None,
+    };
+
+    match module_ns {
+        Some(module_ns) if module_ns != model_ns => {
+            let missive = format!(
+                "⚠️  namespace mismatch for module `{}`: model has {}, {} has {}",
+                module,
+                model_ns,
+                module_file.display(),
+                module_ns
+            );
+            eprintln!("{}", Colour::Yellow.paint(&missive));
+            warn!("{}", missive);
+        }
+        Some(_) => debug!("Namespaces agree for module {}.", module),
+        None => debug!("No UUID_NS found in {}.", module_file.display()),
+    }
+
+    Ok(())
+}
+
+/// Look for generated files that have been edited by hand
+///
+/// Depending on what we've been told, we refuse to continue, back them up, or
+/// just carry on and overwrite them.
+fn check_hand_edits(
+    manifest: &Manifest,
+    module_dir: &Path,
+    module: &str,
+    overwrite: Overwrite,
+    test_mode: bool,
+) -> Result<()> {
+    let modified = manifest.modified_files(module_dir)?;
+    if modified.is_empty() {
+        return Ok(());
+    }
+
+    for path in &modified {
+        eprintln!(
+            "{}",
+            Colour::Yellow.paint(format!("✋ {} was edited by hand", path.display()))
+        );
+    }
+
+    match overwrite {
+        Overwrite::Refuse => anyhow::bail!(
+            "😱 {} generated file(s) in module `{}` were edited by hand. Use --backup to \
+             keep a copy, or --force to overwrite them.",
+            modified.len(),
+            module
+        ),
+        Overwrite::Backup => {
+            for path in &modified {
+                let mut backup = path.clone().into_os_string();
+                backup.push(".orig");
+                println!("  💾 {}", PathBuf::from(&backup).display());
+                if !test_mode {
+                    fs::copy(path, &backup)
+                        .context(format!("😱 Failed to back up file: {:?}", path))?;
+                }
+            }
+        }
+        Overwrite::Force => {
+            warn!("overwriting hand edits in module {}", module);
+        }
+    }
+
+    Ok(())
+}
+
+fn execute_command_verify(modules: &Option<Vec<String>>, dir: &Option<PathBuf>) -> Result<()> {
+    let package_root = find_package_dir(dir)?;
+    let config = load_config(&package_root)?;
+
+    let mut names: Vec<&String> = match modules {
+        Some(modules) => modules.iter().filter(|m| !m.is_empty()).collect(),
+        None => config.modules.keys().collect(),
+    };
+    names.sort();
+
+    let mut count = 0;
+    for name in names {
+        if !config.modules.contains_key(name) {
+            eprintln!(
+                "😱 No module named {} found in {}!",
+                name, SARZAK_CONFIG_TOML
+            );
+            warn!("did not find {} in {}", name, SARZAK_CONFIG_TOML);
+            continue;
+        }
+
+        let mut module_dir = package_root.clone();
+        module_dir.push("src");
+        module_dir.push(name);
+
+        match Manifest::load(&module_dir)? {
+            Some(manifest) => {
+                let modified = manifest.modified_files(&module_dir)?;
+                if modified.is_empty() {
+                    println!("✅ {}", Colour::Blue.paint(name));
+                } else {
+                    println!("✋ {}", Colour::Blue.paint(name));
+                    for path in &modified {
+                        println!("    {}", path.display());
+                    }
+                }
+                count += modified.len();
+            }
+            None => println!("❓ {} has no manifest", Colour::Blue.paint(name)),
+        }
+    }
+
+    if count > 0 {
+        println!(
+            "{}",
+            Colour::Red.paint(format!("\n{} file(s) edited by hand", count))
+        );
+        std::process::exit(FILES_MODIFIED);
     }
+
+    Ok(())
 }
 
 fn find_package_dir(start_dir: &Option<PathBuf>) -> Result<PathBuf> {
@@ -923,3 +3585,63 @@ fn find_package_dir(start_dir: &Option<PathBuf>) -> Result<PathBuf> {
 
     Ok(package_root)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_segments() {
+        assert_eq!(
+            rename_path_segment(
+                "use crate::foo::Bar;\nlet x = crate::foo::baz();",
+                "crate::foo",
+                "crate::qux"
+            ),
+            "use crate::qux::Bar;\nlet x = crate::qux::baz();"
+        );
+    }
+
+    #[test]
+    fn path_segments_that_are_prefixes() {
+        let source = "use crate::foo_bar::Baz;\nuse crate::foobar;\nuse my_crate::foo::Baz;";
+        assert_eq!(
+            rename_path_segment(source, "crate::foo", "crate::qux"),
+            source
+        );
+    }
+
+    #[test]
+    fn use_groups() {
+        assert_eq!(
+            rename_in_use_groups("use crate::{foo, bar};", "crate", "foo", "qux"),
+            "use crate::{qux, bar};"
+        );
+        assert_eq!(
+            rename_in_use_groups(
+                "use crate::{\n    bar,\n    foo::{Baz, store::ObjectStore},\n};",
+                "crate",
+                "foo",
+                "qux"
+            ),
+            "use crate::{\n    bar,\n    qux::{Baz, store::ObjectStore},\n};"
+        );
+    }
+
+    #[test]
+    fn use_groups_that_must_not_change() {
+        for source in [
+            // Prefixes of other names.
+            "use crate::{foo_bar, foobar::Baz};",
+            // Not directly under the prefix.
+            "use crate::{a::{b, foo}, bar::foo};",
+            "use crate::{bar::{foo::Baz}};",
+            // Some other crate.
+            "use other_crate::{foo, bar};",
+            // Something that's only called `foo`.
+            "use crate::{bar as foo};",
+        ] {
+            assert_eq!(rename_in_use_groups(source, "crate", "foo", "qux"), source);
+        }
+    }
+}
@@ -0,0 +1,559 @@
+//! A flattened view of a domain
+//!
+//! The v2 `Domain` is an object store, and getting anything useful out of it
+//! means chasing ids from one type to the next. Exporters, diffs, and the like
+//! don't care about that. They want objects with their attributes, and
+//! relationships with both ends spelled out. That's what this is.
+//!
+//! Everything is in a stable order: objects by their position on the paper,
+//! and relationships by number.
+//!
+//! The view also keeps what cuckoo needs to draw the model the way it was: the
+//! paper, where objects are, and where relationship lines attach to them.
+use std::{cmp::Ordering, collections::BTreeMap};
+
+use sarzak::{
+    drawing::{types::Edge as DEdge, ObjectStore as DrawingStore},
+    sarzak::{
+        types::{Cardinality as SCardinality, Conditionality as SConditionality, Relationship, Ty},
+        ObjectStore as SarzakStore,
+    },
+    v2::domain::Domain,
+};
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModelView {
+    pub name: String,
+    pub description: String,
+    pub domain_ns: Uuid,
+    /// Width and height of the paper
+    pub extents: (i64, i64),
+    /// How far the paper has been scrolled
+    ///
+    /// v2 models don't keep this; it comes from the cuckoo model's paper.
+    pub offset: (i64, i64),
+    pub objects: Vec<ObjectView>,
+    pub relationships: Vec<RelationshipView>,
+    /// Where each relationship's lines attach to its objects, by relationship
+    ///
+    /// The anchors are in the same order as `RelationshipView::objects`, which
+    /// for subtypes is by id, and not the order they were drawn in.
+    /// Relationships that have never been drawn aren't here.
+    pub anchors: BTreeMap<Uuid, Vec<Anchor>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjectView {
+    pub id: Uuid,
+    pub name: String,
+    pub key_letters: String,
+    pub description: String,
+    pub attributes: Vec<AttributeView>,
+    /// Where the object sits on the paper, if it's been drawn
+    pub layout: Option<Rect>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: i64,
+    pub y: i64,
+    pub width: i64,
+    pub height: i64,
+}
+
+/// Where a relationship line meets an object
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Anchor {
+    pub edge: Edge,
+    pub x: i64,
+    pub y: i64,
+    /// Where the relationship's label sits, relative to the anchor
+    pub offset: (i64, i64),
+}
+
+/// The side of an object that an anchor is on
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Edge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttributeView {
+    pub id: Uuid,
+    pub name: String,
+    pub ty: AttributeType,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AttributeType {
+    Boolean,
+    Float,
+    Integer,
+    String,
+    Uuid,
+    /// A reference to another object, by id
+    Object(Uuid),
+    /// Something from outside the domain, by name
+    External(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cardinality {
+    One,
+    Many,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Conditionality {
+    Conditional,
+    Unconditional,
+}
+
+/// One end of a relationship
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Side {
+    pub obj_id: Uuid,
+    /// The relationship phrase, as read from the other end
+    pub description: String,
+    pub cardinality: Cardinality,
+    pub conditionality: Conditionality,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RelationshipView {
+    Binary(BinaryView),
+    Isa(IsaView),
+    Associative(AssociativeView),
+}
+
+/// A binary relationship
+///
+/// `from` is the referrer, the side with the referential attribute, and `to`
+/// is the referent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BinaryView {
+    pub id: Uuid,
+    pub number: i64,
+    pub from: Side,
+    pub to: Side,
+    pub referential_attribute: String,
+}
+
+/// A supertype/subtype relationship
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct IsaView {
+    pub id: Uuid,
+    pub number: i64,
+    pub supertype: Uuid,
+    pub subtypes: Vec<Uuid>,
+}
+
+/// An associative relationship
+///
+/// `from` is the associative object, which formalizes the relationship between
+/// `one` and `other`, with a referential attribute for each.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssociativeView {
+    pub id: Uuid,
+    pub number: i64,
+    pub from: Uuid,
+    pub one: Side,
+    pub other: Side,
+    pub one_referential_attribute: String,
+    pub other_referential_attribute: String,
+}
+
+impl ModelView {
+    pub fn new(domain: &Domain) -> Self {
+        let store = domain.sarzak();
+
+        let mut objects: Vec<ObjectView> = store
+            .iter_object()
+            .map(|object| {
+                let mut attributes: Vec<AttributeView> = store
+                    .iter_attribute()
+                    .filter(|attr| attr.obj_id == object.id)
+                    .map(|attr| AttributeView {
+                        id: attr.id,
+                        name: attr.name.clone(),
+                        ty: attribute_type(store, &attr.ty),
+                    })
+                    .collect();
+                // Identifiers first, and then alphabetical.
+                attributes.sort_by(|a, b| {
+                    (a.name != "id")
+                        .cmp(&(b.name != "id"))
+                        .then_with(|| a.name.cmp(&b.name))
+                });
+
+                ObjectView {
+                    id: object.id,
+                    name: object.name.clone(),
+                    key_letters: object.key_letters.clone(),
+                    description: object.description.clone(),
+                    attributes,
+                    layout: None,
+                }
+            })
+            .collect();
+
+        let drawing = domain.drawing();
+        for ui in drawing.iter_object_ui() {
+            if let (Some(object), Some(origin)) = (
+                objects.iter_mut().find(|o| o.id == ui.object_id),
+                drawing.exhume_point(&ui.origin),
+            ) {
+                object.layout = Some(Rect {
+                    x: origin.x,
+                    y: origin.y,
+                    width: ui.width,
+                    height: ui.height,
+                });
+            }
+        }
+
+        objects.sort_by(layout_order);
+
+        let mut relationships: Vec<RelationshipView> = store
+            .iter_relationship()
+            .filter_map(|rel| relationship(store, rel))
+            .collect();
+        relationships.sort_by_key(|r| (r.number(), r.id()));
+
+        let [width, height] = domain.extents();
+        let anchors = anchors(drawing, &objects, &relationships);
+
+        ModelView {
+            name: domain.name().to_owned(),
+            description: domain.description().to_owned(),
+            domain_ns: *domain.id(),
+            extents: (*width as i64, *height as i64),
+            offset: (0, 0),
+            objects,
+            relationships,
+            anchors,
+        }
+    }
+
+    pub fn object(&self, id: &Uuid) -> Option<&ObjectView> {
+        self.objects.iter().find(|o| &o.id == id)
+    }
+
+    pub fn object_by_name(&self, name: &str) -> Option<&ObjectView> {
+        self.objects.iter().find(|o| o.name == name)
+    }
+
+    /// The name of an object, or its id if we can't find it
+    ///
+    pub fn object_name(&self, id: &Uuid) -> String {
+        self.object(id)
+            .map(|o| o.name.clone())
+            .unwrap_or_else(|| id.to_string())
+    }
+
+    /// The relationships that an object takes part in
+    ///
+    pub fn relationships_of(&self, id: &Uuid) -> Vec<&RelationshipView> {
+        self.relationships
+            .iter()
+            .filter(|r| r.objects().contains(id))
+            .collect()
+    }
+
+    /// The supertype of an object, if it's a subtype
+    ///
+    pub fn supertype_of(&self, id: &Uuid) -> Option<&IsaView> {
+        self.relationships.iter().find_map(|r| match r {
+            RelationshipView::Isa(isa) if isa.subtypes.contains(id) => Some(isa),
+            _ => None,
+        })
+    }
+}
+
+impl RelationshipView {
+    pub fn id(&self) -> Uuid {
+        match self {
+            RelationshipView::Binary(b) => b.id,
+            RelationshipView::Isa(i) => i.id,
+            RelationshipView::Associative(a) => a.id,
+        }
+    }
+
+    pub fn number(&self) -> i64 {
+        match self {
+            RelationshipView::Binary(b) => b.number,
+            RelationshipView::Isa(i) => i.number,
+            RelationshipView::Associative(a) => a.number,
+        }
+    }
+
+    /// The relationship's name, e.g., `R12`
+    ///
+    pub fn name(&self) -> String {
+        format!("R{}", self.number())
+    }
+
+    /// Every object involved in the relationship
+    ///
+    pub fn objects(&self) -> Vec<Uuid> {
+        match self {
+            RelationshipView::Binary(b) => vec![b.from.obj_id, b.to.obj_id],
+            RelationshipView::Isa(i) => {
+                let mut objects = vec![i.supertype];
+                objects.extend(i.subtypes.iter().copied());
+                objects
+            }
+            RelationshipView::Associative(a) => vec![a.from, a.one.obj_id, a.other.obj_id],
+        }
+    }
+}
+
+impl Edge {
+    /// The edge, as cuckoo spells it
+    ///
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Edge::Top => "North",
+            Edge::Bottom => "South",
+            Edge::Left => "West",
+            Edge::Right => "East",
+        }
+    }
+
+    pub fn from_dir(dir: &str) -> Option<Self> {
+        match dir {
+            "North" => Some(Edge::Top),
+            "South" => Some(Edge::Bottom),
+            "West" => Some(Edge::Left),
+            "East" => Some(Edge::Right),
+            _ => None,
+        }
+    }
+}
+
+impl Cardinality {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Cardinality::One => "One",
+            Cardinality::Many => "Many",
+        }
+    }
+}
+
+impl Conditionality {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Conditionality::Conditional => "Conditional",
+            Conditionality::Unconditional => "Unconditional",
+        }
+    }
+}
+
+impl AttributeType {
+    /// The name of the type, as cuckoo spells it
+    ///
+    pub fn name(&self, view: &ModelView) -> String {
+        match self {
+            AttributeType::Boolean => "Boolean".to_owned(),
+            AttributeType::Float => "Float".to_owned(),
+            AttributeType::Integer => "Integer".to_owned(),
+            AttributeType::String => "String".to_owned(),
+            AttributeType::Uuid => "Uuid".to_owned(),
+            AttributeType::Object(id) => view.object_name(id),
+            AttributeType::External(name) => name.clone(),
+        }
+    }
+}
+
+/// Order objects by where they sit on the paper
+///
+/// Top to bottom, then left to right. Objects that haven't been drawn go last,
+/// and ties are broken by name.
+pub fn layout_order(a: &ObjectView, b: &ObjectView) -> Ordering {
+    let position = |o: &ObjectView| o.layout.map(|r| (r.y, r.x));
+    match (position(a), position(b)) {
+        (Some(pa), Some(pb)) => pa.cmp(&pb),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+    .then_with(|| a.name.cmp(&b.name))
+}
+
+/// Collect the relationship anchors from the drawing
+///
+fn anchors(
+    drawing: &DrawingStore,
+    objects: &[ObjectView],
+    relationships: &[RelationshipView],
+) -> BTreeMap<Uuid, Vec<Anchor>> {
+    let anchor = |id: &Uuid| -> Option<Anchor> {
+        let anchor = drawing.exhume_anchor(id)?;
+        let location = drawing.exhume_point(&anchor.location)?;
+        let offset = drawing.exhume_point(&anchor.offset)?;
+        let edge = match drawing.exhume_edge(&anchor.edge)? {
+            DEdge::Top(_) => Edge::Top,
+            DEdge::Bottom(_) => Edge::Bottom,
+            DEdge::Left(_) => Edge::Left,
+            DEdge::Right(_) => Edge::Right,
+        };
+
+        Some(Anchor {
+            edge,
+            x: location.x,
+            y: location.y,
+            offset: (offset.x, offset.y),
+        })
+    };
+
+    let mut anchors = BTreeMap::new();
+    for ui in drawing.iter_binary_ui() {
+        if let Some(ends) = [ui.from, ui.to].iter().map(anchor).collect() {
+            anchors.insert(ui.binary_id, ends);
+        }
+    }
+    for ui in drawing.iter_isa_ui() {
+        let isa = match relationships.iter().find(|r| r.id() == ui.isa_id) {
+            Some(RelationshipView::Isa(isa)) => isa,
+            _ => continue,
+        };
+        let mut subtypes: Vec<Anchor> = drawing
+            .iter_subtype_anchors()
+            .filter(|s| s.isaui_id == ui.id)
+            .filter_map(|s| anchor(&s.anchor_id))
+            .collect();
+        // The drawing doesn't say which subtype an anchor belongs to, so each
+        // subtype gets the anchor that's nearest to it.
+        let subtypes = isa.subtypes.iter().map(|id| {
+            let rect = objects.iter().find(|o| &o.id == id)?.layout?;
+            let nearest = (0..subtypes.len()).min_by_key(|&i| distance(&rect, &subtypes[i]))?;
+            Some(subtypes.swap_remove(nearest))
+        });
+        if let Some(ends) = std::iter::once(anchor(&ui.from)).chain(subtypes).collect() {
+            anchors.insert(ui.isa_id, ends);
+        }
+    }
+    for ui in drawing.iter_associative_ui() {
+        if let Some(ends) = [ui.from, ui.one, ui.other].iter().map(anchor).collect() {
+            anchors.insert(ui.associative_id, ends);
+        }
+    }
+
+    anchors
+}
+
+/// How far an anchor is from an object
+///
+fn distance(rect: &Rect, anchor: &Anchor) -> i64 {
+    let dx = (rect.x - anchor.x).max(anchor.x - (rect.x + rect.width));
+    let dy = (rect.y - anchor.y).max(anchor.y - (rect.y + rect.height));
+
+    dx.max(0) + dy.max(0)
+}
+
+fn attribute_type(store: &SarzakStore, ty: &Uuid) -> AttributeType {
+    match store.exhume_ty(ty) {
+        Some(Ty::Boolean(_)) => AttributeType::Boolean,
+        Some(Ty::Float(_)) => AttributeType::Float,
+        Some(Ty::Integer(_)) => AttributeType::Integer,
+        Some(Ty::SString(_)) => AttributeType::String,
+        Some(Ty::SUuid(_)) => AttributeType::Uuid,
+        Some(Ty::Object(id)) => AttributeType::Object(*id),
+        Some(Ty::External(id)) => AttributeType::External(
+            store
+                .exhume_external(id)
+                .map(|e| e.name.clone())
+                .unwrap_or_else(|| id.to_string()),
+        ),
+        None => AttributeType::External(ty.to_string()),
+    }
+}
+
+fn cardinality(store: &SarzakStore, id: &Uuid) -> Cardinality {
+    match store.exhume_cardinality(id) {
+        Some(SCardinality::Many(_)) => Cardinality::Many,
+        _ => Cardinality::One,
+    }
+}
+
+fn conditionality(store: &SarzakStore, id: &Uuid) -> Conditionality {
+    match store.exhume_conditionality(id) {
+        Some(SConditionality::Conditional(_)) => Conditionality::Conditional,
+        _ => Conditionality::Unconditional,
+    }
+}
+
+fn relationship(store: &SarzakStore, rel: &Relationship) -> Option<RelationshipView> {
+    match rel {
+        Relationship::Binary(id) => {
+            let binary = store.exhume_binary(id)?;
+            let referrer = store.exhume_referrer(&binary.from)?;
+            let referent = store.exhume_referent(&binary.to)?;
+
+            Some(RelationshipView::Binary(BinaryView {
+                id: binary.id,
+                number: binary.number,
+                from: Side {
+                    obj_id: referrer.obj_id,
+                    description: referrer.description.clone(),
+                    cardinality: cardinality(store, &referrer.cardinality),
+                    conditionality: conditionality(store, &referrer.conditionality),
+                },
+                to: Side {
+                    obj_id: referent.obj_id,
+                    description: referent.description.clone(),
+                    cardinality: cardinality(store, &referent.cardinality),
+                    conditionality: conditionality(store, &referent.conditionality),
+                },
+                referential_attribute: referrer.referential_attribute_name.clone(),
+            }))
+        }
+        Relationship::Isa(id) => {
+            let isa = store.exhume_isa(id)?;
+            let supertype = store.exhume_supertype(&isa.supertype)?;
+            let mut subtypes: Vec<Uuid> = store
+                .iter_subtype()
+                .filter(|s| s.isa == isa.id)
+                .map(|s| s.obj_id)
+                .collect();
+            subtypes.sort();
+
+            Some(RelationshipView::Isa(IsaView {
+                id: isa.id,
+                number: isa.number,
+                supertype: supertype.obj_id,
+                subtypes,
+            }))
+        }
+        Relationship::Associative(id) => {
+            let assoc = store.exhume_associative(id)?;
+            let from = store.exhume_associative_referrer(&assoc.from)?;
+            let side = |id: &Uuid| {
+                store.exhume_associative_referent(id).map(|r| Side {
+                    obj_id: r.obj_id,
+                    description: r.description.clone(),
+                    cardinality: cardinality(store, &r.cardinality),
+                    conditionality: conditionality(store, &r.conditionality),
+                })
+            };
+
+            Some(RelationshipView::Associative(AssociativeView {
+                id: assoc.id,
+                number: assoc.number,
+                from: from.obj_id,
+                one: side(&assoc.one)?,
+                other: side(&assoc.other)?,
+                one_referential_attribute: from.one_referential_attribute.clone(),
+                other_referential_attribute: from.other_referential_attribute.clone(),
+            }))
+        }
+    }
+}